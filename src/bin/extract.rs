@@ -0,0 +1,41 @@
+use std::env;
+use std::io;
+use std::process;
+use windwaker::data::GameData;
+use windwaker::gcm::Disc;
+
+// extract builds the crate's data tables from a Wind Waker disc image.
+//
+// usage: extract <disc.iso> [output.json]
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (iso, output) = match args.as_slice() {
+        [iso] => (iso.as_str(), "windwaker.json"),
+        [iso, output] => (iso.as_str(), output.as_str()),
+        _ => {
+            eprintln!("usage: extract <disc.iso> [output.json]");
+            process::exit(2);
+        }
+    };
+
+    let mut disc = Disc::open(iso)?;
+    let data = GameData::extract(&mut disc)?;
+    data.save(output)?;
+
+    println!(
+        "extracted {} ({}): {} stages, {} actors, {} chests, {} messages, {} islands",
+        data.game_id,
+        disc.version(),
+        data.stages.len(),
+        data.actors.len(),
+        data.chests.len(),
+        data.messages.len(),
+        data.islands.len()
+    );
+    for name in &data.skipped {
+        eprintln!("skipped stage {}", name);
+    }
+    println!("wrote {}", output);
+
+    Ok(())
+}
@@ -0,0 +1,360 @@
+use crate::gcm;
+use crate::gcm::{be_u16, be_u32};
+use crate::rarc;
+use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::io::{Read, Seek};
+
+// 803F0A94,2 - The id of the message in the textbox currently on screen.
+pub const MESSAGE_ID: usize = 0x803F0A94;
+// 803F0A9B,1 - Non-zero while a textbox is open.
+pub const TEXTBOX_OPEN: usize = 0x803F0A9B;
+
+// MESSAGE_ARCHIVE holds the game's main message file.
+pub const MESSAGE_ARCHIVE: &str = "res/Msg/bmgres.arc";
+// ITEM_MESSAGE is the message shown when Link gets item 0. Every other item's
+// message follows in item id order.
+pub const ITEM_MESSAGE: u16 = 101;
+
+// BMG files are a 0x20 byte header followed by sections. INF1 lists every
+// message and where its text is, and DAT1 holds the text itself.
+pub const MAGIC: &[u8; 8] = b"MESGbmg1";
+pub const HEADER_SIZE: usize = 0x20;
+const SECTION_HEADER_SIZE: usize = 0x8;
+const INF1_HEADER_SIZE: usize = 0x10;
+
+// control codes start with this byte, followed by their total size in bytes,
+// their group and their code. Anything left over is the code's arguments.
+const CONTROL: u8 = 0x1A;
+const CONTROL_HEADER_SIZE: usize = 5;
+const GROUP_COLOR: u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Legacy,
+    Windows1252,
+    Utf16,
+    ShiftJis,
+    Utf8,
+}
+
+impl Encoding {
+    pub fn new(id: u8) -> io::Result<Self> {
+        match id {
+            0 => Ok(Self::Legacy),
+            1 => Ok(Self::Windows1252),
+            2 => Ok(Self::Utf16),
+            3 => Ok(Self::ShiftJis),
+            4 => Ok(Self::Utf8),
+            _ => Err(gcm::invalid_data("unknown BMG text encoding")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    White,
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Cyan,
+    Magenta,
+    Grey,
+    Orange,
+    Unknown,
+}
+
+impl Color {
+    pub fn new(id: u8) -> Self {
+        match id {
+            0 => Self::White,
+            1 => Self::Red,
+            2 => Self::Green,
+            3 => Self::Blue,
+            4 => Self::Yellow,
+            5 => Self::Cyan,
+            6 => Self::Magenta,
+            7 => Self::Grey,
+            8 => Self::Orange,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+// Segment is one piece of a message: either text to show or one of the
+// control codes embedded in it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Segment {
+    Text(String),
+    PlayerName,
+    Color(Color),
+    Control { group: u8, code: u16, args: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Message {
+    pub id: u16,
+    pub price: u16,
+    pub next: u16,
+    pub textbox: u8,
+    pub draw: u8,
+    pub position: u8,
+    pub item: u8,
+    pub segments: Vec<Segment>,
+}
+
+impl Message {
+    // text returns what the player sees, with the player's name filled in and
+    // every other control code removed.
+    pub fn text(&self, player_name: &str) -> String {
+        self.segments
+            .iter()
+            .map(|s| match s {
+                Segment::Text(text) => text.as_str(),
+                Segment::PlayerName => player_name,
+                _ => "",
+            })
+            .collect()
+    }
+
+    // highlight returns the first coloured run of text, which is how the game
+    // picks out the name of the item in an item message.
+    pub fn highlight(&self) -> Option<String> {
+        let mut text = String::new();
+        let mut coloured = false;
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Color(Color::White) if coloured => break,
+                Segment::Color(Color::White) => {}
+                Segment::Color(_) => coloured = true,
+                Segment::Text(t) if coloured => text.push_str(t),
+                _ => {}
+            }
+        }
+
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text("Link"))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Bmg {
+    pub encoding: Encoding,
+    pub messages: Vec<Message>,
+}
+
+impl Bmg {
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        if buf.len() < HEADER_SIZE || &buf[0..8] != MAGIC {
+            return Err(gcm::invalid_data("not a BMG file"));
+        }
+
+        let sections = be_u32(buf, 0xC)? as usize;
+        let encoding = Encoding::new(buf[0x10])?;
+
+        let mut inf1 = None;
+        let mut dat1 = None;
+        let mut offset = HEADER_SIZE;
+        for _ in 0..sections {
+            let magic = buf
+                .get(offset..offset + 4)
+                .ok_or_else(|| gcm::invalid_data("BMG section is out of range"))?;
+            let size = be_u32(buf, offset + 0x4)? as usize;
+            if size < SECTION_HEADER_SIZE {
+                return Err(gcm::invalid_data("BMG section is too small"));
+            }
+
+            match magic {
+                b"INF1" => inf1 = Some(offset),
+                b"DAT1" => dat1 = Some(offset + SECTION_HEADER_SIZE),
+                _ => {}
+            }
+            offset += size;
+        }
+
+        let (inf1, dat1) = match (inf1, dat1) {
+            (Some(inf1), Some(dat1)) => (inf1, dat1),
+            _ => return Err(gcm::invalid_data("BMG file is missing INF1 or DAT1")),
+        };
+
+        let count = be_u16(buf, inf1 + 0x8)? as usize;
+        let entry_size = be_u16(buf, inf1 + 0xA)? as usize;
+
+        let mut messages = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = inf1 + INF1_HEADER_SIZE + i * entry_size;
+            let text = dat1 + be_u32(buf, entry)? as usize;
+            let b = buf
+                .get(entry + 0xC..entry + 0x10)
+                .ok_or_else(|| gcm::invalid_data("BMG message is out of range"))?;
+
+            messages.push(Message {
+                id: be_u16(buf, entry + 0x4)?,
+                price: be_u16(buf, entry + 0x6)?,
+                next: be_u16(buf, entry + 0x8)?,
+                textbox: b[0],
+                draw: b[1],
+                position: b[2],
+                item: b[3],
+                segments: decode(buf.get(text..).unwrap_or(&[]), encoding)?,
+            });
+        }
+
+        Ok(Self { encoding, messages })
+    }
+
+    pub fn from_disc<R: Read + Seek>(disc: &mut gcm::Disc<R>) -> io::Result<Self> {
+        let archive = rarc::Archive::from_disc(disc, MESSAGE_ARCHIVE)?;
+        let (_, file) = archive
+            .files()
+            .into_iter()
+            .find(|(path, _)| path.ends_with(".bmg"))
+            .ok_or_else(|| gcm::invalid_data("message archive has no BMG file"))?;
+
+        Self::parse(&file.data)
+    }
+
+    pub fn get(&self, id: u16) -> Option<&Message> {
+        self.messages.iter().find(|m| m.id == id)
+    }
+
+    // current returns the message in the textbox on screen, if there is one.
+    pub fn current(&self, d: &Dolphin) -> io::Result<Option<&Message>> {
+        let textbox = Textbox::default().read(d)?;
+
+        Ok(textbox
+            .id
+            .filter(|_| textbox.open)
+            .and_then(|id| self.get(id)))
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Textbox {
+    pub open: bool,
+    pub id: Option<u16>,
+}
+
+impl Textbox {
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.open = d.read_u8(TEXTBOX_OPEN, None)? != 0;
+        self.id = match self.open {
+            true => Some(d.read_u16(MESSAGE_ID, None)?),
+            false => None,
+        };
+
+        Ok(*self)
+    }
+}
+
+// decode splits a null terminated message into text and control codes.
+pub fn decode(buf: &[u8], encoding: Encoding) -> io::Result<Vec<Segment>> {
+    let width = match encoding {
+        Encoding::Utf16 => 2,
+        _ => 1,
+    };
+
+    let mut segments = Vec::new();
+    let mut text = Vec::new();
+    let mut pos = 0;
+    loop {
+        let c = match width {
+            2 => be_u16(buf, pos)?,
+            _ => *buf
+                .get(pos)
+                .ok_or_else(|| gcm::invalid_data("BMG message is not terminated"))?
+                as u16,
+        };
+
+        if c == 0 {
+            break;
+        }
+
+        if c != CONTROL as u16 {
+            text.extend_from_slice(&buf[pos..pos + width]);
+            pos += width;
+            continue;
+        }
+
+        // the size and group are single bytes even in UTF-16 text, so
+        // control codes line up the same way whatever the encoding.
+        let start = pos + width - 1;
+        let size = *buf
+            .get(start + 1)
+            .ok_or_else(|| gcm::invalid_data("BMG control code is out of range"))?
+            as usize;
+        let group = *buf
+            .get(start + 2)
+            .ok_or_else(|| gcm::invalid_data("BMG control code is out of range"))?;
+        let code = be_u16(buf, start + 3)?;
+        // the size includes the whole control character, which is two bytes
+        // wide in UTF-16 text.
+        let end = (pos + size).max(start + CONTROL_HEADER_SIZE);
+        let args = buf
+            .get(start + CONTROL_HEADER_SIZE..end)
+            .ok_or_else(|| gcm::invalid_data("BMG control code is out of range"))?
+            .to_vec();
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text_string(&text, encoding)));
+            text.clear();
+        }
+        segments.push(match (group, code) {
+            (0, 0) => Segment::PlayerName,
+            (GROUP_COLOR, 0) if !args.is_empty() => Segment::Color(Color::new(args[0])),
+            _ => Segment::Control { group, code, args },
+        });
+        pos = end;
+    }
+
+    if !text.is_empty() {
+        segments.push(Segment::Text(text_string(&text, encoding)));
+    }
+
+    Ok(segments)
+}
+
+fn text_string(buf: &[u8], encoding: Encoding) -> String {
+    match encoding {
+        // the western releases only use the printable Latin-1 range.
+        Encoding::Legacy | Encoding::Windows1252 => buf.iter().map(|b| *b as char).collect(),
+        Encoding::Utf16 => {
+            let units: Vec<u16> = buf
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        Encoding::Utf8 => String::from_utf8_lossy(buf).into_owned(),
+        // decoding Shift-JIS needs its full table, so only the ASCII range is
+        // kept and each double byte character is replaced.
+        Encoding::ShiftJis => {
+            let mut out = String::new();
+            let mut iter = buf.iter();
+            while let Some(b) = iter.next() {
+                match b {
+                    0x00..=0x7F => out.push(*b as char),
+                    0x81..=0x9F | 0xE0..=0xFC => {
+                        iter.next();
+                        out.push(char::REPLACEMENT_CHARACTER);
+                    }
+                    _ => out.push(char::REPLACEMENT_CHARACTER),
+                }
+            }
+            out
+        }
+    }
+}
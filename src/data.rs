@@ -0,0 +1,226 @@
+use crate::bmg;
+use crate::dzx;
+use crate::gcm;
+use crate::picto;
+use crate::player;
+use crate::poi;
+use crate::treasure;
+use crate::world;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+// DATA_VERSION is bumped whenever the layout of `GameData` changes, so that
+// files extracted by an older version are rejected rather than misread.
+pub const DATA_VERSION: u32 = 5;
+
+// STAGE_DIR is where each stage's archives are stored on the disc.
+pub const STAGE_DIR: &str = "res/Stage/";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChestLocation {
+    pub stage: String,
+    pub room: u8,
+    pub item: u8,
+    pub item_name: Option<String>,
+    pub opened_flag: u8,
+    pub position: player::Position,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Island {
+    #[serde(with = "world::display_name")]
+    pub quadrant: world::Quadrant,
+    pub position: (f32, f32),
+}
+
+// GameData is the set of tables extracted from the player's own copy of the
+// game. It is written once by the `extract` command and loaded at runtime, so
+// none of the game's data needs to be distributed with the crate.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GameData {
+    pub version: u32,
+    pub game_id: String,
+    pub stages: Vec<String>,
+    pub actors: Vec<String>,
+    pub chests: Vec<ChestLocation>,
+    pub messages: Vec<bmg::Message>,
+    pub islands: Vec<Island>,
+    pub treasure_charts: Vec<treasure::TreasureChart>,
+    pub points_of_interest: Vec<poi::PointOfInterest>,
+    pub subjects: BTreeMap<u8, String>,
+    // stages that are on the disc but couldn't be read, such as unused test
+    // maps missing their stage data.
+    pub skipped: Vec<String>,
+}
+
+impl GameData {
+    // extract reads every table from a disc. Everything is sorted, so the same
+    // disc always produces the same output.
+    pub fn extract<R: Read + Seek>(disc: &mut gcm::Disc<R>) -> io::Result<Self> {
+        let names: BTreeSet<String> = disc
+            .files
+            .iter()
+            .filter_map(|f| {
+                let path = f.path.strip_prefix(STAGE_DIR)?;
+                let (name, file) = path.split_once('/')?;
+
+                (file == "Stage.arc").then(|| name.to_owned())
+            })
+            .collect();
+
+        let mut data = Self {
+            version: DATA_VERSION,
+            game_id: disc.header.game_id().to_string(),
+            stages: Vec::new(),
+            actors: Vec::new(),
+            chests: Vec::new(),
+            messages: bmg::Bmg::from_disc(disc)?.messages,
+            islands: Vec::new(),
+            treasure_charts: Vec::new(),
+            points_of_interest: Vec::new(),
+            subjects: BTreeMap::new(),
+            skipped: Vec::new(),
+        };
+        data.subjects = picto::Subjects::from_messages(&data.messages)
+            .names
+            .into_iter()
+            .collect();
+        let mut sea_rooms = Vec::new();
+
+        let mut actors = BTreeSet::new();
+        for name in names {
+            // unused test maps can be missing their stage or room data, but
+            // anything else means the disc or a parser is broken.
+            let stage = match dzx::Stage::from_disc(disc, &name) {
+                Ok(stage) => stage,
+                Err(err) if err.kind() == io::ErrorKind::NotFound && name != "sea" => {
+                    data.skipped.push(name);
+                    continue;
+                }
+                Err(err) => {
+                    return Err(io::Error::new(
+                        err.kind(),
+                        format!("stage {}: {}", name, err),
+                    ))
+                }
+            };
+
+            let rooms = stage.rooms.values();
+            for actor in stage
+                .stage
+                .actors
+                .iter()
+                .chain(rooms.flat_map(|r| &r.actors))
+            {
+                actors.insert(actor.name.clone());
+            }
+
+            for (room, chest) in stage.chests() {
+                data.chests.push(ChestLocation {
+                    stage: name.clone(),
+                    room,
+                    item: chest.item,
+                    item_name: data.item_name(chest.item),
+                    opened_flag: chest.opened_flag,
+                    position: chest.position,
+                });
+            }
+
+            // the sea's rooms are placed in the world by the stage's MULT chunk.
+            if name == "sea" {
+                for room in &stage.stage.sea_rooms {
+                    if let Some(quadrant) = room.quadrant() {
+                        data.islands.push(Island {
+                            quadrant,
+                            position: (room.x, room.z),
+                        });
+                    }
+                }
+                sea_rooms = stage.stage.sea_rooms.clone();
+                data.points_of_interest = poi::PointsOfInterest::from_stage(&stage).points;
+            }
+
+            data.stages.push(name);
+        }
+        data.actors = actors.into_iter().collect();
+        if sea_rooms.is_empty() {
+            return Err(gcm::invalid_data("disc has no sea stage"));
+        }
+
+        // charts aren't linked to where they're found, so match chests by the
+        // name of the item inside. Charts given out any other way are left
+        // without a location.
+        let mut charts = treasure::TreasureCatalogue::from_disc(disc, &sea_rooms)?.charts;
+        for chart in &mut charts {
+            let name = chart.chart.to_string();
+            chart.found_at = data
+                .chests
+                .iter()
+                .find(|c| {
+                    c.item_name
+                        .as_ref()
+                        .is_some_and(|n| n.eq_ignore_ascii_case(&name))
+                })
+                .map(|c| format!("{} room {}", c.stage, c.room));
+        }
+        data.treasure_charts = charts;
+
+        Ok(data)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data: Self = serde_json::from_slice(&fs::read(path)?)?;
+        if data.version != DATA_VERSION {
+            return Err(gcm::invalid_data(&format!(
+                "game data is version {}, expected {}; extract it again",
+                data.version, DATA_VERSION
+            )));
+        }
+
+        Ok(data)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    // island_positions returns the islands in the form `world::Map` and
+    // `route::RoutePlanner` expect.
+    pub fn island_positions(&self) -> HashMap<world::Quadrant, (f32, f32)> {
+        self.islands
+            .iter()
+            .map(|i| (i.quadrant, i.position))
+            .collect()
+    }
+
+    // map returns the Great Sea with its islands where they really are.
+    pub fn map(&self) -> world::Map {
+        world::Map::with_islands(self.island_positions())
+    }
+
+    pub fn treasure_catalogue(&self) -> treasure::TreasureCatalogue {
+        treasure::TreasureCatalogue::new(self.treasure_charts.clone())
+    }
+
+    pub fn points_of_interest(&self) -> poi::PointsOfInterest {
+        poi::PointsOfInterest::new(self.points_of_interest.clone())
+    }
+
+    pub fn subjects(&self) -> picto::Subjects {
+        picto::Subjects::new(self.subjects.clone().into_iter().collect())
+    }
+
+    pub fn message(&self, id: u16) -> Option<&bmg::Message> {
+        self.messages.iter().find(|m| m.id == id)
+    }
+
+    // item_name returns the name of an item, as highlighted in the message
+    // shown when Link gets it.
+    pub fn item_name(&self, item: u8) -> Option<String> {
+        self.message(bmg::ITEM_MESSAGE + item as u16)?.highlight()
+    }
+}
@@ -0,0 +1,500 @@
+use crate::dzx;
+use crate::gcm;
+use crate::gcm::{be_u16, be_u32};
+use crate::player;
+use crate::rarc;
+use crate::world;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::io;
+use std::io::{Read, Seek};
+
+// DZB files hold a room's collision mesh. The header is a count and offset
+// for each table, and every offset is absolute.
+pub const HEADER_SIZE: usize = 0x34;
+const VERTEX_SIZE: usize = 0xC;
+const TRIANGLE_SIZE: usize = 0xA;
+const GROUP_SIZE: usize = 0x34;
+const PROPERTY_SIZE: usize = 0x10;
+
+// SEA_LEVEL is the height of the water's surface across the Great Sea.
+pub const SEA_LEVEL: f32 = 0.0;
+// MAX_FLOOR_ANGLE is the steepest a surface can be, in degrees, before Link
+// slides down it rather than standing on it.
+pub const MAX_FLOOR_ANGLE: f32 = 45.0;
+
+// outline points are snapped to this many units so that triangles which
+// don't share vertices in the mesh are still joined up.
+const OUTLINE_PRECISION: f32 = 1.0;
+
+// Point is a position on the XZ plane snapped to the outline precision.
+type Point = (i32, i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Attribute {
+    Normal,
+    Dirt,
+    Wood,
+    Stone,
+    Grass,
+    GiantFlower,
+    Lava,
+    Bridge,
+    Electric,
+    Ice,
+    Water,
+    Unknown(u8),
+}
+
+impl Attribute {
+    pub fn new(code: u8) -> Self {
+        match code {
+            0 => Self::Normal,
+            1 => Self::Dirt,
+            2 => Self::Wood,
+            3 => Self::Stone,
+            4 => Self::Grass,
+            5 => Self::GiantFlower,
+            6 => Self::Lava,
+            7 => Self::Bridge,
+            9 => Self::Electric,
+            12 => Self::Ice,
+            19 => Self::Water,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Normal => write!(f, "Normal"),
+            Self::Dirt => write!(f, "Dirt"),
+            Self::Wood => write!(f, "Wood"),
+            Self::Stone => write!(f, "Stone"),
+            Self::Grass => write!(f, "Grass"),
+            Self::GiantFlower => write!(f, "Giant Flower"),
+            Self::Lava => write!(f, "Lava"),
+            Self::Bridge => write!(f, "Bridge"),
+            Self::Electric => write!(f, "Electric"),
+            Self::Ice => write!(f, "Ice"),
+            Self::Water => write!(f, "Water"),
+            Self::Unknown(code) => write!(f, "Unknown ({})", code),
+        }
+    }
+}
+
+// Property is the set of codes shared by triangles with the same surface,
+// packed into bitfields as the game stores them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Property {
+    pub codes: [u32; 4],
+}
+
+impl Property {
+    pub fn exit(&self) -> Option<u8> {
+        match self.codes[0] & 0x3F {
+            0x3F => None,
+            exit => Some(exit as u8),
+        }
+    }
+
+    pub fn wall_code(&self) -> u8 {
+        ((self.codes[1] >> 8) & 0xF) as u8
+    }
+
+    pub fn special_code(&self) -> u8 {
+        ((self.codes[1] >> 12) & 0xF) as u8
+    }
+
+    pub fn attribute(&self) -> Attribute {
+        Attribute::new(((self.codes[1] >> 16) & 0x1F) as u8)
+    }
+
+    pub fn ground_code(&self) -> u8 {
+        ((self.codes[1] >> 21) & 0x1F) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Triangle {
+    pub vertices: [u16; 3],
+    pub property: u16,
+    pub group: u16,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Group {
+    pub name: String,
+    pub parent: Option<u16>,
+    pub room: i16,
+}
+
+// Surface is what lies at a point on the collision mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Surface {
+    pub height: f32,
+    pub attribute: Attribute,
+    pub ground_code: u8,
+    pub special_code: u8,
+    pub exit: Option<u8>,
+    // slope is the angle of the surface from flat, in degrees.
+    pub slope: f32,
+}
+
+impl Surface {
+    pub fn is_slope(&self) -> bool {
+        self.slope > MAX_FLOOR_ANGLE
+    }
+
+    pub fn is_water(&self) -> bool {
+        self.attribute == Attribute::Water
+    }
+}
+
+impl fmt::Display for Surface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at y: {:.2} ({:.0} degrees)",
+            self.attribute, self.height, self.slope
+        )
+    }
+}
+
+// Collision is a stage or room's collision mesh. Its queries take positions in
+// the mesh's own coordinates, which for the sea's rooms are relative to the
+// room, see `dzx::SeaRoom::to_local`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Collision {
+    pub vertices: Vec<(f32, f32, f32)>,
+    pub triangles: Vec<Triangle>,
+    pub groups: Vec<Group>,
+    pub properties: Vec<Property>,
+}
+
+impl Collision {
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        let table = |i: usize| -> io::Result<(usize, usize)> {
+            Ok((
+                be_u32(buf, i * 8)? as usize,
+                be_u32(buf, i * 8 + 4)? as usize,
+            ))
+        };
+
+        let (count, offset) = table(0)?;
+        let vertices = (0..count)
+            .map(|i| {
+                let v = offset + i * VERTEX_SIZE;
+                Ok((f32_at(buf, v)?, f32_at(buf, v + 4)?, f32_at(buf, v + 8)?))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let (count, offset) = table(1)?;
+        let triangles = (0..count)
+            .map(|i| {
+                let t = offset + i * TRIANGLE_SIZE;
+                Ok(Triangle {
+                    vertices: [be_u16(buf, t)?, be_u16(buf, t + 2)?, be_u16(buf, t + 4)?],
+                    property: be_u16(buf, t + 6)?,
+                    group: be_u16(buf, t + 8)?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        // tables 2 and 3 are the spatial index the game uses to speed up its
+        // own queries, which isn't needed here.
+        let (count, offset) = table(4)?;
+        let groups = (0..count)
+            .map(|i| {
+                let g = offset + i * GROUP_SIZE;
+                let name = be_u32(buf, g)? as usize;
+                let parent = be_u16(buf, g + 0x24)?;
+
+                Ok(Group {
+                    name: buf.get(name..).map(gcm::c_string).unwrap_or_default(),
+                    parent: (parent != 0xFFFF).then_some(parent),
+                    room: be_u16(buf, g + 0x2A)? as i16,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let (count, offset) = table(5)?;
+        let properties = (0..count)
+            .map(|i| {
+                let p = offset + i * PROPERTY_SIZE;
+                Ok(Property {
+                    codes: [
+                        be_u32(buf, p)?,
+                        be_u32(buf, p + 4)?,
+                        be_u32(buf, p + 8)?,
+                        be_u32(buf, p + 12)?,
+                    ],
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let collision = Self {
+            vertices,
+            triangles,
+            groups,
+            properties,
+        };
+        if collision.triangles.iter().any(|t| {
+            t.vertices
+                .iter()
+                .any(|v| *v as usize >= collision.vertices.len())
+                || t.property as usize >= collision.properties.len()
+        }) {
+            return Err(gcm::invalid_data("DZB triangle is out of range"));
+        }
+
+        Ok(collision)
+    }
+
+    // from_archive finds and parses the collision in a room archive.
+    pub fn from_archive(archive: &rarc::Archive) -> io::Result<Self> {
+        let (_, file) = archive
+            .files()
+            .into_iter()
+            .find(|(path, _)| path.ends_with(".dzb"))
+            .ok_or_else(|| gcm::invalid_data("archive has no collision"))?;
+
+        Self::parse(&file.data)
+    }
+
+    // ground_height returns the height of the highest floor at the point.
+    pub fn ground_height(&self, x: f32, z: f32) -> Option<f32> {
+        self.floors(x, z)
+            .map(|s| s.height)
+            .max_by(|a, b| a.total_cmp(b))
+    }
+
+    // surface_below returns the floor directly beneath a position, which is
+    // the surface Link is standing on when he is on the ground.
+    pub fn surface_below(&self, position: &player::Position) -> Option<Surface> {
+        self.floors(position.x, position.z)
+            .filter(|s| s.height <= position.y)
+            .max_by(|a, b| a.height.total_cmp(&b.height))
+    }
+
+    // is_on_land returns whether there is ground above the water at the point.
+    pub fn is_on_land(&self, x: f32, z: f32) -> bool {
+        self.floors(x, z)
+            .any(|s| !s.is_water() && s.height > SEA_LEVEL)
+    }
+
+    // floors lists every upward facing surface at the point.
+    fn floors(&self, x: f32, z: f32) -> impl Iterator<Item = Surface> + '_ {
+        self.triangles.iter().filter_map(move |t| {
+            let [a, b, c] = self.corners(t);
+            let normal = normal(a, b, c);
+            if normal.1 <= 0.0 {
+                return None;
+            }
+
+            let (u, v, w) = barycentric((x, z), (a.0, a.2), (b.0, b.2), (c.0, c.2))?;
+            let property = self.properties[t.property as usize];
+
+            Some(Surface {
+                height: a.1 * u + b.1 * v + c.1 * w,
+                attribute: property.attribute(),
+                ground_code: property.ground_code(),
+                special_code: property.special_code(),
+                exit: property.exit(),
+                slope: normal.1.clamp(-1.0, 1.0).acos().to_degrees(),
+            })
+        })
+    }
+
+    fn corners(&self, t: &Triangle) -> [(f32, f32, f32); 3] {
+        t.vertices.map(|v| self.vertices[v as usize])
+    }
+
+    // outline traces the coastline of every island in the mesh: the edges of
+    // the land above sea level, projected onto the XZ plane as closed loops.
+    pub fn outline(&self) -> Vec<Vec<(f32, f32)>> {
+        let snap = |(x, _, z): (f32, f32, f32)| {
+            (
+                (x / OUTLINE_PRECISION).round() as i32,
+                (z / OUTLINE_PRECISION).round() as i32,
+            )
+        };
+
+        // an edge used by exactly one land triangle is on the coast.
+        let mut edges: HashMap<(Point, Point), usize> = HashMap::new();
+        for t in &self.triangles {
+            let corners = self.corners(t);
+            let property = self.properties[t.property as usize];
+            if property.attribute() == Attribute::Water || corners.iter().all(|c| c.1 <= SEA_LEVEL)
+            {
+                continue;
+            }
+
+            let points = corners.map(snap);
+            for i in 0..3 {
+                let (a, b) = (points[i], points[(i + 1) % 3]);
+                if a == b {
+                    continue;
+                }
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+
+        let mut next: HashMap<Point, Vec<Point>> = HashMap::new();
+        for ((a, b), _) in edges.into_iter().filter(|(_, n)| *n == 1) {
+            next.entry(a).or_default().push(b);
+            next.entry(b).or_default().push(a);
+        }
+
+        let mut loops = Vec::new();
+        let mut starts: Vec<Point> = next.keys().copied().collect();
+        starts.sort_unstable();
+        for start in starts {
+            let mut points = Vec::new();
+            let mut current = start;
+            while let Some(to) = next.get_mut(&current).and_then(|n| n.pop()) {
+                if let Some(back) = next.get_mut(&to) {
+                    back.retain(|p| *p != current);
+                }
+                points.push(current);
+                current = to;
+                if current == start {
+                    break;
+                }
+            }
+
+            if points.len() >= 3 {
+                loops.push(
+                    points
+                        .into_iter()
+                        .map(|(x, z)| (x as f32 * OUTLINE_PRECISION, z as f32 * OUTLINE_PRECISION))
+                        .collect(),
+                );
+            }
+        }
+
+        loops
+    }
+}
+
+// Outline is the coastline of the islands in one quadrant of the Great Sea.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Outline {
+    #[serde(with = "world::display_name")]
+    pub quadrant: world::Quadrant,
+    pub loops: Vec<Vec<(f32, f32)>>,
+}
+
+impl Outline {
+    // to_svg_path draws the outline as an SVG path on the given chart.
+    pub fn to_svg_path(&self, chart: &world::Chart) -> String {
+        let mut path = String::new();
+        for points in &self.loops {
+            for (i, (x, z)) in points.iter().enumerate() {
+                let (px, py) = chart.to_pixel(*x, *z);
+                let command = if i == 0 { 'M' } else { 'L' };
+                let _ = write!(path, "{}{:.1} {:.1} ", command, px, py);
+            }
+            path.push_str("Z ");
+        }
+
+        path.trim_end().to_owned()
+    }
+}
+
+// island_outlines reads the collision of each of the sea's rooms and traces
+// its islands. Each room's collision is relative to the room, so its outline
+// is placed in the world by the room's entry in the sea stage's MULT chunk.
+pub fn island_outlines<R: Read + Seek>(disc: &mut gcm::Disc<R>) -> io::Result<Vec<Outline>> {
+    let sea = rarc::Archive::from_disc(disc, "res/Stage/sea/Stage.arc")?;
+    let rooms = dzx::find_dzx(&sea, ".dzs")?.sea_rooms;
+
+    let mut outlines = Vec::with_capacity(rooms.len());
+    for room in rooms {
+        let path = format!("res/Stage/sea/Room{}.arc", room.room);
+        let quadrant = match room.quadrant() {
+            Some(quadrant) if disc.find(&path).is_some() => quadrant,
+            _ => continue,
+        };
+
+        let collision = Collision::from_archive(&rarc::Archive::from_disc(disc, &path)?)?;
+        let loops = collision
+            .outline()
+            .into_iter()
+            .map(|points| {
+                points
+                    .into_iter()
+                    .map(|(x, z)| room.to_world(x, z))
+                    .collect()
+            })
+            .collect();
+
+        outlines.push(Outline { quadrant, loops });
+    }
+    outlines.sort_by_key(|o| world::Quadrant::ALL.iter().position(|q| *q == o.quadrant));
+
+    Ok(outlines)
+}
+
+// to_svg renders outlines as an SVG image of the whole sea chart.
+pub fn to_svg(outlines: &[Outline], chart: &world::Chart) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        chart.width, chart.height
+    );
+    for outline in outlines {
+        let _ = writeln!(
+            svg,
+            "  <path id=\"{:?}\" fill-rule=\"evenodd\" d=\"{}\"/>",
+            outline.quadrant,
+            outline.to_svg_path(chart)
+        );
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+fn f32_at(buf: &[u8], offset: usize) -> io::Result<f32> {
+    Ok(f32::from_bits(be_u32(buf, offset)?))
+}
+
+fn normal(a: (f32, f32, f32), b: (f32, f32, f32), c: (f32, f32, f32)) -> (f32, f32, f32) {
+    let u = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+    let v = (c.0 - a.0, c.1 - a.1, c.2 - a.2);
+    let n = (
+        u.1 * v.2 - u.2 * v.1,
+        u.2 * v.0 - u.0 * v.2,
+        u.0 * v.1 - u.1 * v.0,
+    );
+    let length = (n.0 * n.0 + n.1 * n.1 + n.2 * n.2).sqrt();
+    if length == 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+
+    (n.0 / length, n.1 / length, n.2 / length)
+}
+
+// barycentric returns the weights of each corner of a 2D triangle at the
+// point, or None if the point is outside of it.
+fn barycentric(
+    p: (f32, f32),
+    a: (f32, f32),
+    b: (f32, f32),
+    c: (f32, f32),
+) -> Option<(f32, f32, f32)> {
+    let det = (b.1 - c.1) * (a.0 - c.0) + (c.0 - b.0) * (a.1 - c.1);
+    if det == 0.0 {
+        return None;
+    }
+
+    let u = ((b.1 - c.1) * (p.0 - c.0) + (c.0 - b.0) * (p.1 - c.1)) / det;
+    let v = ((c.1 - a.1) * (p.0 - c.0) + (a.0 - c.0) * (p.1 - c.1)) / det;
+    let w = 1.0 - u - v;
+
+    (u >= 0.0 && v >= 0.0 && w >= 0.0).then_some((u, v, w))
+}
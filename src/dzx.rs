@@ -0,0 +1,646 @@
+use crate::gcm;
+use crate::gcm::{be_u16, be_u32};
+use crate::player;
+use crate::rarc;
+use crate::stage;
+use crate::world;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::io::{Read, Seek};
+
+// DZS (stage) and DZR (room) files share one format: a count of chunks
+// followed by a header for each, giving its four character tag, how many
+// entries it holds and the offset of the first. Every offset is absolute.
+pub const CHUNK_HEADER_SIZE: usize = 0xC;
+
+const ACTOR_SIZE: usize = 0x20;
+const SCALED_ACTOR_SIZE: usize = 0x24;
+const CHEST_SIZE: usize = 0x20;
+const SPAWN_SIZE: usize = 0x20;
+const EXIT_SIZE: usize = 0xC;
+const ROOM_TABLE_ENTRY_SIZE: usize = 0x8;
+const SEA_ROOM_SIZE: usize = 0xC;
+
+// ACTOR_NAMES maps the short internal names actors are placed by to
+// something readable. Names missing from the table are left as they are, and
+// what a chest holds is resolved with `data::GameData::item_name`.
+pub const ACTOR_NAMES: [(&str, &str); 62] = [
+    ("Bk", "Bokoblin"),
+    ("Mo2", "Moblin"),
+    ("Tn", "Darknut"),
+    ("Rdead1", "ReDead"),
+    ("Rdead2", "ReDead"),
+    ("magtail", "Magtail"),
+    ("p_hat", "Peahat"),
+    ("Oq", "Octorok"),
+    ("Ki", "Keese"),
+    ("Bb", "Kargaroc"),
+    ("Pw", "Poe"),
+    ("Am", "Armos"),
+    ("Am2", "Armos Knight"),
+    ("wiz_r", "Wizzrobe"),
+    ("Fm", "Floormaster"),
+    ("nz", "Rat"),
+    ("kuro_s", "Morth"),
+    ("kuro_t", "Morth"),
+    ("c_green", "Green ChuChu"),
+    ("c_red", "Red ChuChu"),
+    ("c_blue", "Blue ChuChu"),
+    ("c_black", "Dark ChuChu"),
+    ("c_kiiro", "Yellow ChuChu"),
+    ("Daiocta", "Big Octo"),
+    ("Oship", "Gunboat"),
+    ("Btd", "Gohma"),
+    ("Bmd", "Kalle Demos"),
+    ("Bdk", "Helmaroc King"),
+    ("Bst", "Gohdan"),
+    ("Bpw", "Jalhalla"),
+    ("Bwd", "Molgera"),
+    ("Bgn", "Puppet Ganon"),
+    ("Gnd", "Ganondorf"),
+    ("Link", "Link"),
+    ("kamome", "Seagull"),
+    ("tsubo", "Pot"),
+    ("Kanban", "Sign"),
+    ("Salvage", "Salvage Point"),
+    ("KNOB00", "Door"),
+    ("KNOB01", "Door"),
+    ("door10", "Dungeon Door"),
+    ("door12", "Boss Door"),
+    ("takara", "Chest"),
+    ("takara2", "Chest"),
+    ("takara3", "Chest"),
+    ("takara4", "Chest"),
+    ("takara5", "Chest"),
+    ("takara6", "Chest"),
+    ("takara7", "Chest"),
+    ("takara8", "Chest"),
+    ("takaraI", "Chest"),
+    ("takaraK", "Chest"),
+    ("takaraM", "Chest"),
+    ("tkrAGc", "Chest"),
+    ("tkrAIk", "Chest"),
+    ("tkrAKd", "Chest"),
+    ("tkrASw", "Chest"),
+    ("tkrBAc", "Chest"),
+    ("tkrBMs", "Chest"),
+    ("tkrCTf", "Chest"),
+    ("tkrOJn", "Chest"),
+    ("tkrOSw", "Chest"),
+];
+
+pub fn actor_description(name: &str) -> Option<&'static str> {
+    ACTOR_NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, description)| *description)
+}
+
+// Layer is the set of actors loaded alongside the base layout. Chunks without
+// a layer are loaded whichever layer the stage is on.
+pub type Layer = Option<u8>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActorKind {
+    Actor,
+    Scaled,
+    Tag,
+    TagScaled,
+    Door,
+    TagDoor,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Actor {
+    pub kind: ActorKind,
+    pub name: String,
+    pub params: u32,
+    pub position: player::Position,
+    pub rotation: (u16, u16, u16),
+    pub enemy_number: u16,
+    pub scale: Option<(u8, u8, u8)>,
+    pub layer: Layer,
+}
+
+impl Actor {
+    fn parse(buf: &[u8], offset: usize, kind: ActorKind, layer: Layer) -> io::Result<Self> {
+        let scale = match kind {
+            ActorKind::Scaled | ActorKind::TagScaled | ActorKind::Door | ActorKind::TagDoor => {
+                let b = bytes(buf, offset + 0x20, 3)?;
+                Some((b[0], b[1], b[2]))
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            kind,
+            name: name(buf, offset)?,
+            params: be_u32(buf, offset + 0x8)?,
+            position: position(buf, offset + 0xC)?,
+            rotation: (
+                be_u16(buf, offset + 0x18)?,
+                be_u16(buf, offset + 0x1A)?,
+                be_u16(buf, offset + 0x1C)?,
+            ),
+            enemy_number: be_u16(buf, offset + 0x1E)?,
+            scale,
+            layer,
+        })
+    }
+
+    pub fn description(&self) -> &str {
+        actor_description(&self.name).unwrap_or(&self.name)
+    }
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.description(), self.position)
+    }
+}
+
+// Chest is a treasure chest placement, which records the item inside and the
+// flag the game sets once it has been opened.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Chest {
+    pub name: String,
+    pub params: u32,
+    pub position: player::Position,
+    pub room: u16,
+    pub rotation: u16,
+    pub item: u8,
+    pub opened_flag: u8,
+    pub layer: Layer,
+}
+
+impl Chest {
+    fn parse(buf: &[u8], offset: usize, layer: Layer) -> io::Result<Self> {
+        let b = bytes(buf, offset + 0x1C, 2)?;
+
+        Ok(Self {
+            name: name(buf, offset)?,
+            params: be_u32(buf, offset + 0x8)?,
+            position: position(buf, offset + 0xC)?,
+            room: be_u16(buf, offset + 0x18)?,
+            rotation: be_u16(buf, offset + 0x1A)?,
+            item: b[0],
+            opened_flag: b[1],
+            layer,
+        })
+    }
+}
+
+impl fmt::Display for Chest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Chest (item {:#04X}) at {}", self.item, self.position)
+    }
+}
+
+// Spawn is a point the player can enter a stage at, selected by the spawn id
+// of a `stage::StageInfo`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Spawn {
+    pub id: u8,
+    pub room: u8,
+    pub event: u8,
+    pub kind: u8,
+    pub position: player::Position,
+    pub rotation: u16,
+    pub layer: Layer,
+}
+
+impl Spawn {
+    fn parse(buf: &[u8], offset: usize, layer: Layer) -> io::Result<Self> {
+        let params = be_u32(buf, offset + 0x8)?;
+
+        Ok(Self {
+            id: bytes(buf, offset + 0x1D, 1)?[0],
+            room: ((params >> 16) & 0x3F) as u8,
+            event: params as u8,
+            kind: ((params >> 12) & 0xF) as u8,
+            position: position(buf, offset + 0xC)?,
+            rotation: be_u16(buf, offset + 0x1A)?,
+            layer,
+        })
+    }
+
+    // stage_info returns the location used to enter the given stage at this
+    // spawn, suitable for `stage::StageInfo::write_next`.
+    pub fn stage_info(&self, stage: &str) -> stage::StageInfo {
+        stage::StageInfo::new(stage, self.room as i8, self.id as i16, self.layer_index())
+    }
+
+    fn layer_index(&self) -> i8 {
+        self.layer.map_or(-1, |layer| layer as i8)
+    }
+}
+
+impl fmt::Display for Spawn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Spawn {} (room {}) at {}",
+            self.id, self.room, self.position
+        )
+    }
+}
+
+// Exit is a destination the stage can send the player to. Exits are referred
+// to by their index, for example by loading zones and doors.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Exit {
+    pub stage: String,
+    pub spawn: u8,
+    pub room: u8,
+    pub fade: u8,
+    pub layer: Layer,
+}
+
+impl Exit {
+    fn parse(buf: &[u8], offset: usize, layer: Layer) -> io::Result<Self> {
+        let b = bytes(buf, offset + 0x8, 3)?;
+
+        Ok(Self {
+            stage: name(buf, offset)?,
+            spawn: b[0],
+            room: b[1],
+            fade: b[2],
+            layer,
+        })
+    }
+
+    pub fn is_known_stage(&self) -> bool {
+        stage::is_known_stage(&self.stage)
+    }
+
+    // stage_info returns where this exit leads. The layer is left for the game
+    // to choose (-1), as it does when the player takes the exit.
+    pub fn stage_info(&self) -> stage::StageInfo {
+        stage::StageInfo::new(&self.stage, self.room as i8, self.spawn as i16, -1)
+    }
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (room {}, spawn {})",
+            self.stage, self.room, self.spawn
+        )
+    }
+}
+
+// RoomTable is the stage's list of which rooms are loaded alongside each
+// room, indexed by room number.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct RoomTable {
+    pub rooms: Vec<Vec<u8>>,
+}
+
+impl RoomTable {
+    fn parse(buf: &[u8], offset: usize, count: usize) -> io::Result<Self> {
+        let mut rooms = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = be_u32(buf, offset + i * 4)? as usize;
+            let b = bytes(buf, entry, ROOM_TABLE_ENTRY_SIZE)?;
+            let list = be_u32(buf, entry + 0x4)? as usize;
+
+            // the low six bits are the room number, the rest are flags.
+            let adjacent = bytes(buf, list, b[0] as usize)?
+                .iter()
+                .map(|r| r & 0x3F)
+                .collect();
+            rooms.push(adjacent);
+        }
+
+        Ok(Self { rooms })
+    }
+
+    pub fn adjacent(&self, room: u8) -> &[u8] {
+        self.rooms.get(room as usize).map_or(&[], |r| r.as_slice())
+    }
+}
+
+// SeaRoom places one of the sea stage's rooms in the world, which is how
+// each room number lines up with a quadrant of the Great Sea.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct SeaRoom {
+    pub room: u8,
+    pub x: f32,
+    pub z: f32,
+    pub rotation: u16,
+}
+
+impl SeaRoom {
+    fn parse(buf: &[u8], offset: usize) -> io::Result<Self> {
+        Ok(Self {
+            x: f32::from_bits(be_u32(buf, offset)?),
+            z: f32::from_bits(be_u32(buf, offset + 0x4)?),
+            rotation: be_u16(buf, offset + 0x8)?,
+            room: bytes(buf, offset + 0xA, 1)?[0],
+        })
+    }
+
+    // quadrant returns the square the room fills. The sea's rooms are
+    // numbered from 1 in `world::Quadrant::ALL` order.
+    pub fn quadrant(&self) -> Option<world::Quadrant> {
+        world::Quadrant::ALL
+            .get((self.room as usize).wrapping_sub(1))
+            .copied()
+    }
+
+    // to_world places an XZ position from the room's own data in the world,
+    // turning it by the room's rotation and moving it to the room's origin.
+    pub fn to_world(&self, x: f32, z: f32) -> (f32, f32) {
+        let angle = self.rotation as f32 * std::f32::consts::TAU / 65536.0;
+        let (sin, cos) = angle.sin_cos();
+
+        (self.x + x * cos + z * sin, self.z - x * sin + z * cos)
+    }
+
+    // to_local is the inverse of `to_world`, for querying the room's own data,
+    // such as its collision, at a position in the world.
+    pub fn to_local(&self, x: f32, z: f32) -> (f32, f32) {
+        let angle = self.rotation as f32 * std::f32::consts::TAU / 65536.0;
+        let (sin, cos) = angle.sin_cos();
+        let (dx, dz) = (x - self.x, z - self.z);
+
+        (dx * cos - dz * sin, dx * sin + dz * cos)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Chunk {
+    pub tag: String,
+    pub count: usize,
+    pub offset: usize,
+}
+
+// Dzx is a parsed DZS or DZR file. Chunks this parser doesn't understand are
+// still listed in `chunks` so they can be found by tag.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Dzx {
+    pub chunks: Vec<Chunk>,
+    pub actors: Vec<Actor>,
+    pub chests: Vec<Chest>,
+    pub spawns: Vec<Spawn>,
+    pub exits: Vec<Exit>,
+    pub rooms: RoomTable,
+    pub sea_rooms: Vec<SeaRoom>,
+}
+
+impl Dzx {
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        let count = be_u32(buf, 0)? as usize;
+        let mut dzx = Self::default();
+
+        for i in 0..count {
+            let header = 0x4 + i * CHUNK_HEADER_SIZE;
+            let chunk = Chunk {
+                tag: String::from_utf8_lossy(bytes(buf, header, 4)?).into_owned(),
+                count: be_u32(buf, header + 0x4)? as usize,
+                offset: be_u32(buf, header + 0x8)? as usize,
+            };
+
+            dzx.parse_chunk(buf, &chunk)?;
+            dzx.chunks.push(chunk);
+        }
+
+        Ok(dzx)
+    }
+
+    fn parse_chunk(&mut self, buf: &[u8], chunk: &Chunk) -> io::Result<()> {
+        let (base, layer) = split_tag(&chunk.tag);
+
+        let entries = |size: usize| (0..chunk.count).map(move |i| chunk.offset + i * size);
+        match base {
+            "ACT" | "TGO" => {
+                let kind = match base {
+                    "ACT" => ActorKind::Actor,
+                    _ => ActorKind::Tag,
+                };
+                for offset in entries(ACTOR_SIZE) {
+                    self.actors.push(Actor::parse(buf, offset, kind, layer)?);
+                }
+            }
+            "SCO" | "TGS" | "DOO" | "Doo" | "TGD" => {
+                let kind = match base {
+                    "SCO" => ActorKind::Scaled,
+                    "TGS" => ActorKind::TagScaled,
+                    "DOO" | "Doo" => ActorKind::Door,
+                    _ => ActorKind::TagDoor,
+                };
+                for offset in entries(SCALED_ACTOR_SIZE) {
+                    self.actors.push(Actor::parse(buf, offset, kind, layer)?);
+                }
+            }
+            "TRE" => {
+                for offset in entries(CHEST_SIZE) {
+                    self.chests.push(Chest::parse(buf, offset, layer)?);
+                }
+            }
+            "PLY" => {
+                for offset in entries(SPAWN_SIZE) {
+                    self.spawns.push(Spawn::parse(buf, offset, layer)?);
+                }
+            }
+            "SCL" => {
+                for offset in entries(EXIT_SIZE) {
+                    self.exits.push(Exit::parse(buf, offset, layer)?);
+                }
+            }
+            "RTB" => self.rooms = RoomTable::parse(buf, chunk.offset, chunk.count)?,
+            "MUL" => {
+                for offset in entries(SEA_ROOM_SIZE) {
+                    self.sea_rooms.push(SeaRoom::parse(buf, offset)?);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    // on_layer returns a copy holding only what is loaded on the given layer.
+    pub fn on_layer(&self, layer: u8) -> Self {
+        let loaded = |l: &Layer| l.is_none() || *l == Some(layer);
+
+        Self {
+            chunks: self.chunks.clone(),
+            actors: self
+                .actors
+                .iter()
+                .filter(|a| loaded(&a.layer))
+                .cloned()
+                .collect(),
+            chests: self
+                .chests
+                .iter()
+                .filter(|c| loaded(&c.layer))
+                .cloned()
+                .collect(),
+            spawns: self
+                .spawns
+                .iter()
+                .filter(|s| loaded(&s.layer))
+                .cloned()
+                .collect(),
+            exits: self
+                .exits
+                .iter()
+                .filter(|e| loaded(&e.layer))
+                .cloned()
+                .collect(),
+            rooms: self.rooms.clone(),
+            sea_rooms: self.sea_rooms.clone(),
+        }
+    }
+
+    pub fn spawn(&self, id: u8) -> Option<&Spawn> {
+        self.spawns.iter().find(|s| s.id == id)
+    }
+
+    pub fn exit(&self, index: usize) -> Option<&Exit> {
+        self.exits.get(index)
+    }
+
+    // layers lists every layer that has something on it.
+    pub fn layers(&self) -> Vec<u8> {
+        let mut layers: Vec<u8> = self
+            .actors
+            .iter()
+            .map(|a| a.layer)
+            .chain(self.chests.iter().map(|c| c.layer))
+            .chain(self.spawns.iter().map(|s| s.layer))
+            .chain(self.exits.iter().map(|e| e.layer))
+            .flatten()
+            .collect();
+        layers.sort_unstable();
+        layers.dedup();
+
+        layers
+    }
+}
+
+// Stage is a stage's layout along with each of its rooms, as stored in
+// `res/Stage/<name>/Stage.arc` and `Room<n>.arc`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Stage {
+    pub name: String,
+    pub stage: Dzx,
+    pub rooms: BTreeMap<u8, Dzx>,
+}
+
+impl Stage {
+    pub fn from_disc<R: Read + Seek>(disc: &mut gcm::Disc<R>, name: &str) -> io::Result<Self> {
+        let dir = format!("res/Stage/{}/", name);
+        let stage = find_dzx(
+            &rarc::Archive::from_disc(disc, &format!("{}Stage.arc", dir))?,
+            ".dzs",
+        )?;
+
+        let rooms: Vec<(u8, String)> = disc
+            .files
+            .iter()
+            .filter_map(|f| {
+                let file = f.path.strip_prefix(&dir)?;
+                let room = file.strip_prefix("Room")?.strip_suffix(".arc")?;
+
+                Some((room.parse().ok()?, f.path.clone()))
+            })
+            .collect();
+
+        let mut stage = Self {
+            name: name.to_owned(),
+            stage,
+            rooms: BTreeMap::new(),
+        };
+        for (room, path) in rooms {
+            let archive = rarc::Archive::from_disc(disc, &path)?;
+            stage.rooms.insert(room, find_dzx(&archive, ".dzr")?);
+        }
+
+        Ok(stage)
+    }
+
+    pub fn room(&self, room: u8) -> Option<&Dzx> {
+        self.rooms.get(&room)
+    }
+
+    // spawn looks up a spawn by id, which may be placed in the stage or in any
+    // of its rooms.
+    pub fn spawn(&self, id: u8) -> Option<&Spawn> {
+        self.stage
+            .spawn(id)
+            .or_else(|| self.rooms.values().find_map(|r| r.spawn(id)))
+    }
+
+    // exits lists every exit in the stage and its rooms, with the room it is
+    // in, or None for exits belonging to the stage as a whole.
+    pub fn exits(&self) -> Vec<(Option<u8>, &Exit)> {
+        self.stage
+            .exits
+            .iter()
+            .map(|e| (None, e))
+            .chain(
+                self.rooms
+                    .iter()
+                    .flat_map(|(room, r)| r.exits.iter().map(move |e| (Some(*room), e))),
+            )
+            .collect()
+    }
+
+    pub fn chests(&self) -> Vec<(u8, &Chest)> {
+        self.rooms
+            .iter()
+            .flat_map(|(room, r)| r.chests.iter().map(move |c| (*room, c)))
+            .collect()
+    }
+}
+
+pub(crate) fn find_dzx(archive: &rarc::Archive, extension: &str) -> io::Result<Dzx> {
+    let (_, file) = archive
+        .files()
+        .into_iter()
+        .find(|(path, _)| path.ends_with(extension))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "archive has no stage data"))?;
+
+    Dzx::parse(&file.data)
+}
+
+// split_tag separates a chunk tag into the three character type shared by its
+// layers and the layer it's on, given by a last character of 0-9 or a-b.
+fn split_tag(tag: &str) -> (&str, Layer) {
+    if tag.len() != 4 || !tag.is_char_boundary(3) {
+        return (tag, None);
+    }
+
+    let (base, last) = tag.split_at(3);
+    let layer = match last.as_bytes()[0] {
+        b @ b'0'..=b'9' => Some(b - b'0'),
+        b @ b'a'..=b'b' => Some(b - b'a' + 10),
+        _ => None,
+    };
+
+    (base, layer)
+}
+
+fn bytes(buf: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    buf.get(offset..offset + len)
+        .ok_or_else(|| gcm::invalid_data("unexpected end of data"))
+}
+
+fn name(buf: &[u8], offset: usize) -> io::Result<String> {
+    Ok(gcm::c_string(bytes(buf, offset, 8)?))
+}
+
+fn position(buf: &[u8], offset: usize) -> io::Result<player::Position> {
+    Ok(player::Position::new(
+        f32::from_bits(be_u32(buf, offset)?),
+        f32::from_bits(be_u32(buf, offset + 0x4)?),
+        f32::from_bits(be_u32(buf, offset + 0x8)?),
+    ))
+}
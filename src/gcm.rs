@@ -1,8 +1,12 @@
 use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::fs;
 use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
 
-pub const SUPPORTED_GAME_IDS: [&'static str; 2] = ["GZLEO1", "GZLE99"];
+pub const SUPPORTED_GAME_IDS: [&'static str; 2] = ["GZLE01", "GZLE99"];
 pub const HEADER: usize = 0x80000000;
 
 // is_supported_wind_waker returns whether the currently running emulated game is
@@ -45,3 +49,646 @@ impl fmt::Display for GameId {
         write!(f, "{}", self.0)
     }
 }
+
+// Disc image layout, see https://wiki.tockdom.com/wiki/Image_Formats for details.
+pub const BOOT_OFFSET: u64 = 0x0;
+pub const BOOT_SIZE: usize = 0x440;
+pub const BI2_OFFSET: u64 = 0x440;
+pub const BI2_SIZE: usize = 0x2000;
+pub const APPLOADER_OFFSET: u64 = 0x2440;
+pub const APPLOADER_HEADER_SIZE: usize = 0x20;
+pub const DISC_MAGIC: u32 = 0xC2339F3D;
+pub const FST_ENTRY_SIZE: usize = 0xC;
+
+// the DOL header lists the file offsets of its 7 text and 11 data sections,
+// followed by each of their sizes.
+const DOL_HEADER_SIZE: usize = 0x100;
+const DOL_SECTIONS: usize = 18;
+const DOL_SIZES_OFFSET: usize = 0x90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Version {
+    Usa,
+    Europe,
+    Japan,
+    KioskDemo,
+    Unknown,
+}
+
+impl Version {
+    pub fn new(game_id: &str) -> Self {
+        match game_id {
+            "GZLE01" => Self::Usa,
+            "GZLP01" => Self::Europe,
+            "GZLJ01" => Self::Japan,
+            "GZLE99" => Self::KioskDemo,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Usa => write!(f, "USA"),
+            Self::Europe => write!(f, "Europe"),
+            Self::Japan => write!(f, "Japan"),
+            Self::KioskDemo => write!(f, "Kiosk Demo"),
+            Self::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+// Header is the disc's boot.bin.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Header {
+    pub game_code: String,
+    pub maker_code: String,
+    pub disc_number: u8,
+    pub revision: u8,
+    pub name: String,
+    pub dol_offset: u32,
+    pub fst_offset: u32,
+    pub fst_size: u32,
+    pub fst_max_size: u32,
+}
+
+impl Header {
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        if buf.len() < BOOT_SIZE || be_u32(buf, 0x1C)? != DISC_MAGIC {
+            return Err(invalid_data("not a GameCube disc image"));
+        }
+
+        Ok(Self {
+            game_code: c_string(&buf[0x0..0x4]),
+            maker_code: c_string(&buf[0x4..0x6]),
+            disc_number: buf[0x6],
+            revision: buf[0x7],
+            name: c_string(&buf[0x20..0x400]),
+            dol_offset: be_u32(buf, 0x420)?,
+            fst_offset: be_u32(buf, 0x424)?,
+            fst_size: be_u32(buf, 0x428)?,
+            fst_max_size: be_u32(buf, 0x42C)?,
+        })
+    }
+
+    pub fn game_id(&self) -> GameId {
+        GameId::new(&format!("{}{}", self.game_code, self.maker_code))
+    }
+
+    pub fn version(&self) -> Version {
+        Version::new(&String::from(self.game_id()))
+    }
+}
+
+// Bi2 is the disc's bi2.bin, which holds the settings for the debug monitor.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Bi2 {
+    pub debug_monitor_size: u32,
+    pub simulated_memory_size: u32,
+    pub argument_offset: u32,
+    pub debug_flag: u32,
+    pub track_location: u32,
+    pub track_size: u32,
+    pub country_code: u32,
+}
+
+impl Bi2 {
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        if buf.len() < 0x1C {
+            return Err(invalid_data("bi2.bin is too short"));
+        }
+
+        Ok(Self {
+            debug_monitor_size: be_u32(buf, 0x0)?,
+            simulated_memory_size: be_u32(buf, 0x4)?,
+            argument_offset: be_u32(buf, 0x8)?,
+            debug_flag: be_u32(buf, 0xC)?,
+            track_location: be_u32(buf, 0x10)?,
+            track_size: be_u32(buf, 0x14)?,
+            country_code: be_u32(buf, 0x18)?,
+        })
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Apploader {
+    pub date: String,
+    pub entry_point: u32,
+    pub size: u32,
+    pub trailer_size: u32,
+}
+
+impl Apploader {
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        if buf.len() < APPLOADER_HEADER_SIZE {
+            return Err(invalid_data("apploader header is too short"));
+        }
+
+        Ok(Self {
+            date: c_string(&buf[0x0..0x10]),
+            entry_point: be_u32(buf, 0x10)?,
+            size: be_u32(buf, 0x14)?,
+            trailer_size: be_u32(buf, 0x18)?,
+        })
+    }
+
+    // total_size is the size of the apploader on disc, including its header.
+    pub fn total_size(&self) -> usize {
+        APPLOADER_HEADER_SIZE + self.size as usize + self.trailer_size as usize
+    }
+}
+
+// File is a file in the disc's file system table. The path is relative to the
+// root of the file system, such as `res/Stage/sea/Room11.arc`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct File {
+    pub path: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+// parse_fst reads the file system table into the files it lists, in the
+// order they appear in the table.
+pub fn parse_fst(buf: &[u8]) -> io::Result<Vec<File>> {
+    if buf.len() < FST_ENTRY_SIZE {
+        return Err(invalid_data("file system table is too short"));
+    }
+
+    // the root entry's length is the total number of entries, and the string
+    // table of names immediately follows the last entry.
+    let count = be_u32(buf, 0x8)? as usize;
+    let strings = count
+        .checked_mul(FST_ENTRY_SIZE)
+        .filter(|end| *end <= buf.len())
+        .ok_or_else(|| invalid_data("file system table is truncated"))?;
+
+    let mut files = Vec::new();
+    // each open directory is the index after its last child and its path.
+    let mut directories: Vec<(usize, String)> = vec![(count, String::new())];
+
+    for i in 1..count {
+        while directories.len() > 1 && directories.last().is_some_and(|(end, _)| i >= *end) {
+            directories.pop();
+        }
+
+        let entry = &buf[i * FST_ENTRY_SIZE..(i + 1) * FST_ENTRY_SIZE];
+        let name_offset = strings + (be_u32(entry, 0x0)? & 0x00FFFFFF) as usize;
+        let name = buf
+            .get(name_offset..)
+            .map(c_string)
+            .ok_or_else(|| invalid_data("file system table name is out of range"))?;
+
+        let parent = directories
+            .last()
+            .map(|(_, path)| path.clone())
+            .unwrap_or_default();
+        let path = if parent.is_empty() {
+            name
+        } else {
+            format!("{}/{}", parent, name)
+        };
+
+        if entry[0] != 0 {
+            directories.push((be_u32(entry, 0x8)? as usize, path));
+        } else {
+            files.push(File {
+                path,
+                offset: be_u32(entry, 0x4)?,
+                size: be_u32(entry, 0x8)?,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+// Disc reads a GameCube disc image (GCM/ISO) without needing an emulator.
+pub struct Disc<R> {
+    reader: R,
+    pub header: Header,
+    pub bi2: Bi2,
+    pub apploader: Apploader,
+    pub files: Vec<File>,
+}
+
+impl Disc<fs::File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(fs::File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> Disc<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let header = Header::parse(&read_at(&mut reader, BOOT_OFFSET, BOOT_SIZE)?)?;
+        let bi2 = Bi2::parse(&read_at(&mut reader, BI2_OFFSET, BI2_SIZE)?)?;
+        let apploader = Apploader::parse(&read_at(
+            &mut reader,
+            APPLOADER_OFFSET,
+            APPLOADER_HEADER_SIZE,
+        )?)?;
+        let fst = read_at(
+            &mut reader,
+            header.fst_offset as u64,
+            header.fst_size as usize,
+        )?;
+        let files = parse_fst(&fst)?;
+
+        Ok(Self {
+            reader,
+            header,
+            bi2,
+            apploader,
+            files,
+        })
+    }
+
+    pub fn version(&self) -> Version {
+        self.header.version()
+    }
+
+    // find looks up a file by its path. Paths may optionally start with `/` or
+    // `files/`, matching the layout of a disc extracted by Dolphin.
+    pub fn find(&self, path: &str) -> Option<&File> {
+        let path = path.trim_start_matches('/');
+        let path = path.strip_prefix("files/").unwrap_or(path);
+
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    pub fn read_file(&mut self, path: &str) -> io::Result<Vec<u8>> {
+        let file = self
+            .find(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_owned()))?;
+
+        read_at(&mut self.reader, file.offset as u64, file.size as usize)
+    }
+
+    // extract writes a file from the disc to the given destination on disk.
+    pub fn extract<P: AsRef<Path>>(&mut self, path: &str, dest: P) -> io::Result<()> {
+        let data = self.read_file(path)?;
+        if let Some(parent) = dest.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(dest, data)
+    }
+
+    pub fn read_boot(&mut self) -> io::Result<Vec<u8>> {
+        read_at(&mut self.reader, BOOT_OFFSET, BOOT_SIZE)
+    }
+
+    pub fn read_bi2(&mut self) -> io::Result<Vec<u8>> {
+        read_at(&mut self.reader, BI2_OFFSET, BI2_SIZE)
+    }
+
+    pub fn read_apploader(&mut self) -> io::Result<Vec<u8>> {
+        let size = self.apploader.total_size();
+
+        read_at(&mut self.reader, APPLOADER_OFFSET, size)
+    }
+
+    // read_dol reads the main executable, sized from the sections in its header.
+    pub fn read_dol(&mut self) -> io::Result<Vec<u8>> {
+        let offset = self.header.dol_offset as u64;
+        let header = read_at(&mut self.reader, offset, DOL_HEADER_SIZE)?;
+        let mut size = DOL_HEADER_SIZE as u32;
+        for i in 0..DOL_SECTIONS {
+            let end = be_u32(&header, i * 4)?
+                .checked_add(be_u32(&header, DOL_SIZES_OFFSET + i * 4)?)
+                .ok_or_else(|| invalid_data("DOL section is out of range"))?;
+            size = size.max(end);
+        }
+
+        read_at(&mut self.reader, offset, size as usize)
+    }
+
+    pub fn read_fst(&mut self) -> io::Result<Vec<u8>> {
+        let (offset, size) = (self.header.fst_offset, self.header.fst_size);
+
+        read_at(&mut self.reader, offset as u64, size as usize)
+    }
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, size: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; size];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+
+    Ok(buf)
+}
+
+// be_u16 and be_u32 read big endian integers, failing rather than panicking
+// when the data is too short.
+pub(crate) fn be_u16(buf: &[u8], offset: usize) -> io::Result<u16> {
+    offset
+        .checked_add(2)
+        .and_then(|end| buf.get(offset..end))
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid_data("unexpected end of data"))
+}
+
+pub(crate) fn be_u32(buf: &[u8], offset: usize) -> io::Result<u32> {
+    offset
+        .checked_add(4)
+        .and_then(|end| buf.get(offset..end))
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid_data("unexpected end of data"))
+}
+
+// c_string reads a null terminated string, ignoring anything after the null.
+pub(crate) fn c_string(buf: &[u8]) -> String {
+    let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+// the DOL and FST are aligned more strictly than the file data that follows them.
+const SYSTEM_ALIGNMENT: u64 = 0x100;
+pub const DEFAULT_FILE_ALIGNMENT: u64 = 0x20;
+
+enum Source {
+    Disc(File),
+    Data(Vec<u8>),
+}
+
+struct Node {
+    name: String,
+    source: Option<Source>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn directory(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            source: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, path: &str, source: Source) {
+        match path.split_once('/') {
+            Some((dir, rest)) => {
+                let index = match self.children.iter().position(|c| c.name == dir) {
+                    Some(index) => index,
+                    None => {
+                        self.children.push(Self::directory(dir));
+                        self.children.len() - 1
+                    }
+                };
+
+                self.children[index].insert(rest, source);
+            }
+            None => match self.children.iter_mut().find(|c| c.name == path) {
+                Some(node) => node.source = Some(source),
+                None => self.children.push(Self {
+                    name: path.to_owned(),
+                    source: Some(source),
+                    children: Vec::new(),
+                }),
+            },
+        }
+    }
+
+    // sort orders every directory the way Nintendo's tools do, by name
+    // ignoring case, with files and directories mixed together.
+    fn sort(&mut self) {
+        self.children.sort_by_key(|c| c.name.to_ascii_lowercase());
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+}
+
+// Rebuilder writes a new disc image from an original one, replacing or adding
+// files along the way. Everything else, including the boot files, the DOL and
+// untouched files, is copied from the original image.
+pub struct Rebuilder<'a, R> {
+    disc: &'a mut Disc<R>,
+    files: Vec<(String, Vec<u8>)>,
+    alignment: u64,
+}
+
+impl<'a, R: Read + Seek> Rebuilder<'a, R> {
+    pub fn new(disc: &'a mut Disc<R>) -> Self {
+        Self {
+            disc,
+            files: Vec::new(),
+            alignment: DEFAULT_FILE_ALIGNMENT,
+        }
+    }
+
+    // insert replaces the file at the path, or adds it if it doesn't exist.
+    pub fn insert(&mut self, path: &str, data: Vec<u8>) -> &mut Self {
+        let path = path.trim_start_matches('/');
+        let path = path.strip_prefix("files/").unwrap_or(path).to_owned();
+
+        self.files.retain(|(p, _)| *p != path);
+        self.files.push((path, data));
+
+        self
+    }
+
+    // alignment sets the alignment of each file's data on the disc, which must
+    // be a power of two of at least 4 for the console to be able to read it.
+    pub fn alignment(&mut self, alignment: u64) -> &mut Self {
+        self.alignment = alignment.max(4).next_power_of_two();
+
+        self
+    }
+
+    pub fn write<W: Write + Seek>(&mut self, out: &mut W) -> io::Result<()> {
+        let mut root = Node::directory("");
+        for file in &self.disc.files {
+            root.insert(&file.path, Source::Disc(file.clone()));
+        }
+        for (path, data) in &self.files {
+            root.insert(path, Source::Data(data.clone()));
+        }
+        root.sort();
+
+        let apploader = self.disc.read_apploader()?;
+        let dol = self.disc.read_dol()?;
+
+        let dol_offset = align(APPLOADER_OFFSET + apploader.len() as u64, SYSTEM_ALIGNMENT);
+        let fst_offset = align(dol_offset + dol.len() as u64, SYSTEM_ALIGNMENT);
+
+        let mut entries = Vec::new();
+        let mut names = Vec::new();
+        let mut sources = Vec::new();
+        flatten(root, 0, &mut entries, &mut names, &mut sources);
+
+        let fst_size = (entries.len() * FST_ENTRY_SIZE + names.len()) as u64;
+        let mut offset = align(fst_offset + fst_size, self.alignment);
+        let mut layout = Vec::new();
+        for (entry, source) in sources {
+            let size = match &source {
+                Source::Disc(file) => file.size as u64,
+                Source::Data(data) => data.len() as u64,
+            };
+
+            entries[entry][4..8].copy_from_slice(&(offset as u32).to_be_bytes());
+            entries[entry][8..12].copy_from_slice(&(size as u32).to_be_bytes());
+            layout.push((offset, source));
+            offset = align(offset + size, self.alignment);
+        }
+
+        if offset > u32::MAX as u64 {
+            return Err(invalid_data("rebuilt disc image is too large"));
+        }
+
+        let mut boot = self.disc.read_boot()?;
+        boot[0x420..0x424].copy_from_slice(&(dol_offset as u32).to_be_bytes());
+        boot[0x424..0x428].copy_from_slice(&(fst_offset as u32).to_be_bytes());
+        boot[0x428..0x42C].copy_from_slice(&(fst_size as u32).to_be_bytes());
+        boot[0x42C..0x430].copy_from_slice(&(fst_size as u32).to_be_bytes());
+
+        write_at(out, BOOT_OFFSET, &boot)?;
+        write_at(out, BI2_OFFSET, &self.disc.read_bi2()?)?;
+        write_at(out, APPLOADER_OFFSET, &apploader)?;
+        write_at(out, dol_offset, &dol)?;
+        write_at(out, fst_offset, &entries.concat())?;
+        out.write_all(&names)?;
+
+        for (offset, source) in layout {
+            let data = match source {
+                Source::Disc(file) => read_at(
+                    &mut self.disc.reader,
+                    file.offset as u64,
+                    file.size as usize,
+                )?,
+                Source::Data(data) => data,
+            };
+
+            write_at(out, offset, &data)?;
+        }
+
+        // pad the image out to the end of the last file's alignment.
+        let end = out.seek(SeekFrom::End(0))?;
+        if end < offset {
+            out.write_all(&vec![0u8; (offset - end) as usize])?;
+        }
+
+        out.flush()
+    }
+
+    // write_to_path writes the rebuilt image to a file and verifies it.
+    pub fn write_to_path<P: AsRef<Path>>(&mut self, path: P) -> io::Result<Disc<fs::File>> {
+        let expected = self.expected_files();
+        let mut out = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        self.write(&mut out)?;
+        out.seek(SeekFrom::Start(0))?;
+
+        let mut rebuilt = Disc::new(out)?;
+        verify(&mut rebuilt, &self.disc.header, &expected)?;
+
+        Ok(rebuilt)
+    }
+
+    // expected_files lists every path the rebuilt image should contain, along
+    // with the contents of each file that is being replaced or added.
+    fn expected_files(&self) -> Vec<(String, Option<Vec<u8>>)> {
+        let mut expected: Vec<_> = self
+            .disc
+            .files
+            .iter()
+            .filter(|f| !self.files.iter().any(|(p, _)| *p == f.path))
+            .map(|f| (f.path.clone(), None))
+            .collect();
+        expected.extend(
+            self.files
+                .iter()
+                .map(|(p, data)| (p.clone(), Some(data.clone()))),
+        );
+
+        expected
+    }
+}
+
+// verify re-parses a rebuilt image and checks that it describes the same game
+// as the original and contains every expected file.
+pub fn verify<R: Read + Seek>(
+    disc: &mut Disc<R>,
+    original: &Header,
+    expected: &[(String, Option<Vec<u8>>)],
+) -> io::Result<()> {
+    if disc.header.game_code != original.game_code || disc.header.maker_code != original.maker_code
+    {
+        return Err(invalid_data("rebuilt disc has a different game id"));
+    }
+
+    if disc.files.len() != expected.len() {
+        return Err(invalid_data("rebuilt disc has the wrong number of files"));
+    }
+
+    for (path, data) in expected {
+        let contents = disc.read_file(path)?;
+        if let Some(data) = data {
+            if contents != *data {
+                return Err(invalid_data(&format!("{} does not match", path)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// flatten appends the node and its descendants to the FST in depth first
+// order, recording which entries are files and where their data comes from.
+fn flatten(
+    node: Node,
+    parent: usize,
+    entries: &mut Vec<[u8; FST_ENTRY_SIZE]>,
+    names: &mut Vec<u8>,
+    sources: &mut Vec<(usize, Source)>,
+) {
+    let index = entries.len();
+    let mut entry = [0u8; FST_ENTRY_SIZE];
+    // the root entry has no name, so it doesn't take up any of the string table.
+    if index != 0 {
+        entry[0..4].copy_from_slice(&(names.len() as u32).to_be_bytes());
+        names.extend_from_slice(node.name.as_bytes());
+        names.push(0);
+    }
+
+    match node.source {
+        Some(source) => {
+            entries.push(entry);
+            sources.push((index, source));
+        }
+        None => {
+            entry[0] = 1;
+            entry[4..8].copy_from_slice(&(parent as u32).to_be_bytes());
+            entries.push(entry);
+
+            for child in node.children {
+                flatten(child, index, entries, names, sources);
+            }
+
+            let next = entries.len() as u32;
+            entries[index][8..12].copy_from_slice(&next.to_be_bytes());
+        }
+    }
+}
+
+fn align(offset: u64, alignment: u64) -> u64 {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+fn write_at<W: Write + Seek>(out: &mut W, offset: u64, buf: &[u8]) -> io::Result<()> {
+    out.seek(SeekFrom::Start(offset))?;
+    out.write_all(buf)
+}
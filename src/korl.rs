@@ -1,3 +1,6 @@
+use crate::player;
+use crate::world;
+use bit_field::BitField;
 use dolphin_memory::Dolphin;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -6,7 +9,28 @@ use std::io;
 pub const KORL_PTR: usize = 0x803CA75C;
 pub const KORL_SPEED_OFFSET: usize = 0x254;
 
+pub const KORL_X_OFFSET: usize = 0x1F8;
 pub const KORL_Y_OFFSET: usize = 0x1FC;
+pub const KORL_Z_OFFSET: usize = 0x200;
+// 2 bytes - the angle the boat is facing, see `world::bearing_from_angle`.
+pub const KORL_HEADING_OFFSET: usize = 0x206;
+// 12 bytes - the boat's velocity as three floats (x, y, z).
+pub const KORL_VELOCITY_OFFSET: usize = 0x220;
+// 1 byte - what the boat is currently being used for, see `ShipMode`.
+pub const KORL_MODE_OFFSET: usize = 0x0358;
+// 4 bytes - bitfield of the boat's state.
+//   bit 0 - Link is aboard
+//   bit 1 - the boat is stuck against collision
+pub const KORL_STATE_OFFSET: usize = 0x03A8;
+
+// 803C4CA2,2 - Wind direction as set by the Wind's Requiem. An angle where
+// 0x10000 is a full turn, 0 blows south (+Z) and 0x4000 blows east (+X).
+pub const WIND_ANGLE: usize = 0x803C4CA2;
+// 803D8E54,4 - Wind strength. Float from 0 (calm) to 1 (strongest).
+pub const WIND_STRENGTH: usize = 0x803D8E54;
+
+// the wind is favourable while it blows within this many degrees of the heading.
+const FAVOURABLE_WIND_DEGREES: f32 = 90.0;
 
 #[derive(Default, Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct Height(f32);
@@ -55,3 +79,171 @@ impl fmt::Display for Speed {
         write!(f, "{:.2}", self.0)
     }
 }
+
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Wind {
+    pub angle: u16,
+    pub strength: f32,
+}
+
+impl Wind {
+    pub fn new(angle: u16, strength: f32) -> Self {
+        Self { angle, strength }
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.angle = d.read_u16(WIND_ANGLE, None)?;
+        self.strength = d.read_f32(WIND_STRENGTH, None)?;
+
+        Ok(*self)
+    }
+
+    // write_direction points the wind towards the given compass point, the
+    // same way conducting the Wind's Requiem does.
+    pub fn write_direction(&mut self, direction: world::Compass, d: &Dolphin) -> io::Result<Self> {
+        let angle = world::angle_from_bearing(direction.bearing());
+        d.write(&angle.to_be_bytes(), WIND_ANGLE, None)?;
+        self.angle = angle;
+
+        Ok(*self)
+    }
+
+    // bearing returns the direction the wind blows towards in degrees
+    // clockwise from north.
+    pub fn bearing(&self) -> f32 {
+        world::bearing_from_angle(self.angle)
+    }
+
+    pub fn direction(&self) -> world::Compass {
+        world::Compass::new(self.bearing())
+    }
+
+    // is_favourable returns whether the wind is behind a boat sailing on the
+    // given bearing, which is when the sail gives the boat its full speed.
+    pub fn is_favourable(&self, bearing: f32) -> bool {
+        let difference = (self.bearing() - bearing).rem_euclid(360.0);
+
+        difference.min(360.0 - difference) < FAVOURABLE_WIND_DEGREES
+    }
+}
+
+impl fmt::Display for Wind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:.0}°) {:.2}",
+            self.direction(),
+            self.bearing(),
+            self.strength
+        )
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShipMode {
+    // sail down, paddling or drifting
+    #[default]
+    Idle,
+    Sailing,
+    Cannon,
+    // the grappling hook used as a salvage crane
+    Crane,
+    Unknown,
+}
+
+impl ShipMode {
+    pub fn new(mode: u8) -> Self {
+        match mode {
+            0x0 => Self::Idle,
+            0x1 => Self::Sailing,
+            0x2 => Self::Cannon,
+            0x3 => Self::Crane,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for ShipMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Idle => write!(f, "Idle"),
+            Self::Sailing => write!(f, "Sailing"),
+            Self::Cannon => write!(f, "Cannon"),
+            Self::Crane => write!(f, "Crane"),
+            Self::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct KingOfRedLions {
+    pub position: player::Position,
+    pub heading: u16,
+    pub velocity: (f32, f32, f32),
+    pub speed: Speed,
+    pub mode: ShipMode,
+    pub link_aboard: bool,
+    pub stuck: bool,
+}
+
+impl KingOfRedLions {
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.position = player::Position::new(
+            d.read_f32(KORL_PTR, Some(&[KORL_X_OFFSET]))?,
+            d.read_f32(KORL_PTR, Some(&[KORL_Y_OFFSET]))?,
+            d.read_f32(KORL_PTR, Some(&[KORL_Z_OFFSET]))?,
+        );
+        self.heading = d.read_u16(KORL_PTR, Some(&[KORL_HEADING_OFFSET]))?;
+        self.velocity = (
+            d.read_f32(KORL_PTR, Some(&[KORL_VELOCITY_OFFSET]))?,
+            d.read_f32(KORL_PTR, Some(&[KORL_VELOCITY_OFFSET + 0x4]))?,
+            d.read_f32(KORL_PTR, Some(&[KORL_VELOCITY_OFFSET + 0x8]))?,
+        );
+        self.speed = Speed::default().read(d)?;
+        self.mode = ShipMode::new(d.read_u8(KORL_PTR, Some(&[KORL_MODE_OFFSET]))?);
+
+        let state = d.read_u32(KORL_PTR, Some(&[KORL_STATE_OFFSET]))?;
+        self.link_aboard = state.get_bit(0);
+        self.stuck = state.get_bit(1);
+
+        Ok(*self)
+    }
+
+    // bearing returns the direction the boat is facing in degrees clockwise from north.
+    pub fn bearing(&self) -> f32 {
+        world::bearing_from_angle(self.heading)
+    }
+
+    pub fn direction(&self) -> world::Compass {
+        world::Compass::new(self.bearing())
+    }
+
+    pub fn is_sail_up(&self) -> bool {
+        self.mode == ShipMode::Sailing
+    }
+}
+
+impl fmt::Display for KingOfRedLions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} heading: {} ({:.0}°) speed: {} mode: {}",
+            self.position,
+            self.direction(),
+            self.bearing(),
+            self.speed,
+            self.mode
+        )?;
+
+        if !self.link_aboard {
+            write!(f, " (unboarded)")?;
+        }
+
+        if self.stuck {
+            write!(f, " (stuck)")?;
+        }
+
+        Ok(())
+    }
+}
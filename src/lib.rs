@@ -1,6 +1,31 @@
+pub mod bmg;
+pub mod data;
+pub mod dzb;
+pub mod dzx;
 pub mod gcm;
 pub mod input;
 pub mod korl;
+pub mod picto;
 pub mod player;
+pub mod poi;
+pub mod rarc;
+pub mod route;
+pub mod scene;
 pub mod stage;
+pub mod texture;
+pub mod treasure;
+pub mod voyage;
 pub mod world;
+pub mod yaz0;
+
+use dolphin_memory::Dolphin;
+use std::io;
+
+// read_u64 reads a big-endian u64, such as a 64 bit flag field, from two
+// consecutive u32 reads.
+pub(crate) fn read_u64(address: usize, d: &Dolphin) -> io::Result<u64> {
+    let high = d.read_u32(address, None)? as u64;
+    let low = d.read_u32(address + 0x4, None)? as u64;
+
+    Ok((high << 32) | low)
+}
@@ -0,0 +1,165 @@
+use crate::bmg;
+use crate::texture;
+use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+// 803C5130,1 - The number of photos stored in the Picto Box.
+pub const PICTO_COUNT: usize = 0x803C5130;
+// 803C5131,3 - The subject the game recognised in each stored photo, used to
+// decide which figurine Carlov will make from it.
+pub const PICTO_SUBJECTS: usize = 0x803C5131;
+// 80B6A9A0 - The stored photos, one after another.
+pub const PICTO_IMAGES: usize = 0x80B6A9A0;
+// 80B80BA0 - The photo in the viewfinder, kept after the shutter is pressed
+// until it is saved or discarded.
+pub const PICTO_VIEWFINDER: usize = 0x80B80BA0;
+// 803CA8E4,1 - The subject recognised in the viewfinder photo.
+pub const PICTO_VIEWFINDER_SUBJECT: usize = 0x803CA8E4;
+
+pub const PICTO_SLOTS: usize = 3;
+pub const PICTO_WIDTH: usize = 160;
+pub const PICTO_HEIGHT: usize = 120;
+pub const PICTO_FORMAT: texture::Format = texture::Format::RGB565;
+
+// SUBJECT_MESSAGE is the message naming subject 0, the figurine Carlov makes
+// from it. Every other subject's message follows in id order.
+pub const SUBJECT_MESSAGE: u16 = 0x2EE0;
+
+// subjects are stored as 0xFF when the game didn't recognise anything.
+const NO_SUBJECT: u8 = 0xFF;
+
+#[derive(Default, Debug, Clone)]
+pub struct Photo {
+    // None for the photo in the viewfinder.
+    pub slot: Option<usize>,
+    pub subject: Option<u8>,
+    pub image: texture::Image,
+}
+
+impl Photo {
+    pub fn read(&mut self, slot: usize, d: &Dolphin) -> io::Result<Self> {
+        if slot >= PICTO_SLOTS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("picto slot {} is out of range", slot),
+            ));
+        }
+
+        let size = PICTO_FORMAT.data_size(PICTO_WIDTH, PICTO_HEIGHT);
+        self.slot = Some(slot);
+        self.subject = subject(d.read_u8(PICTO_SUBJECTS + slot, None)?);
+        self.image = texture::read(
+            d,
+            PICTO_IMAGES + slot * size,
+            PICTO_WIDTH,
+            PICTO_HEIGHT,
+            PICTO_FORMAT,
+            None,
+        )?;
+
+        Ok(self.clone())
+    }
+
+    pub fn read_viewfinder(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.slot = None;
+        self.subject = subject(d.read_u8(PICTO_VIEWFINDER_SUBJECT, None)?);
+        self.image = texture::read(
+            d,
+            PICTO_VIEWFINDER,
+            PICTO_WIDTH,
+            PICTO_HEIGHT,
+            PICTO_FORMAT,
+            None,
+        )?;
+
+        Ok(self.clone())
+    }
+}
+
+impl fmt::Display for Photo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.slot {
+            Some(slot) => write!(f, "Photo {}", slot + 1)?,
+            None => write!(f, "Viewfinder")?,
+        }
+
+        match self.subject {
+            Some(subject) => write!(f, " (subject {:#04X})", subject),
+            None => write!(f, " (no subject)"),
+        }
+    }
+}
+
+// PictoBox is every photo the player is currently holding.
+#[derive(Default, Debug, Clone)]
+pub struct PictoBox {
+    pub photos: Vec<Photo>,
+}
+
+impl PictoBox {
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        let count = (d.read_u8(PICTO_COUNT, None)? as usize).min(PICTO_SLOTS);
+        self.photos = (0..count)
+            .map(|slot| Photo::default().read(slot, d))
+            .collect::<io::Result<_>>()?;
+
+        Ok(self.clone())
+    }
+
+    // subjects lists the subject of each stored photo that has one.
+    pub fn subjects(&self) -> Vec<u8> {
+        self.photos.iter().filter_map(|p| p.subject).collect()
+    }
+}
+
+// Subjects names the subjects the game can recognise in a photo, such as the
+// figurine each one unlocks in the Nintendo Gallery.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Subjects {
+    pub names: HashMap<u8, String>,
+}
+
+impl Subjects {
+    pub fn new(names: HashMap<u8, String>) -> Self {
+        Self { names }
+    }
+
+    // from_messages names every subject that has a message, using its
+    // highlighted name or otherwise its first line.
+    pub fn from_messages(messages: &[bmg::Message]) -> Self {
+        let names = (0..NO_SUBJECT)
+            .filter_map(|id| {
+                let message = messages
+                    .iter()
+                    .find(|m| m.id == SUBJECT_MESSAGE + id as u16)?;
+                let name = message.highlight().or_else(|| {
+                    let text = message.text("Link");
+                    let line = text.lines().next()?.trim();
+
+                    (!line.is_empty()).then(|| line.to_owned())
+                })?;
+
+                Some((id, name))
+            })
+            .collect();
+
+        Self { names }
+    }
+
+    pub fn name(&self, photo: &Photo) -> Option<&str> {
+        photo
+            .subject
+            .and_then(|s| self.names.get(&s))
+            .map(|s| s.as_str())
+    }
+}
+
+fn subject(id: u8) -> Option<u8> {
+    match id {
+        NO_SUBJECT => None,
+        id => Some(id),
+    }
+}
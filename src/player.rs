@@ -30,7 +30,7 @@ pub const PLAYER_HP_MAX: usize = 0x803C4C08;
 pub const PLAYER_MP: usize = 0x803C4C1C;
 pub const PLAYER_MP_MAX: usize = 0x803C4C1B;
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
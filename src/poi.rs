@@ -0,0 +1,167 @@
+use crate::dzx;
+use crate::stage;
+use crate::world;
+use bit_field::BitField;
+use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+// 803C4F88,24 - The Great Sea's saved stage memory. While Link is at sea the
+// game works on a copy of it in STAGE_MEMORY, and writes it back when he leaves.
+pub const SEA_MEMORY: usize = 0x803C4F88;
+// 803C5380,24 - The memory of the stage Link is in.
+pub const STAGE_MEMORY: usize = 0x803C5380;
+pub const STAGE_MEMORY_SIZE: usize = 0x24;
+// a stage's 128 switches follow its chest flags, as four words.
+const SWITCHES_OFFSET: usize = 0x8;
+// switch 0xFF means an actor isn't tied to a switch.
+const NO_SWITCH: u8 = 0xFF;
+
+// POI_ACTORS are the sea actors that are points of interest, along with the
+// shift of the switch in their params that the game sets once they have been
+// cleared. Submarines, lookout platforms, cannon reefs and light rings aren't
+// tracked yet, as the actors they are placed by haven't been identified.
+pub const POI_ACTORS: [(&str, Kind, u32); 2] =
+    [("Daiocta", Kind::BigOcto, 0), ("Oship", Kind::Gunboat, 0)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    BigOcto,
+    Gunboat,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::BigOcto => write!(f, "Big Octo"),
+            Self::Gunboat => write!(f, "Gunboat"),
+        }
+    }
+}
+
+// Flag is a single bit of game memory, such as one of the sea stage's switch
+// bits, which the game sets once a point of interest has been cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Flag {
+    pub address: usize,
+    pub bit: usize,
+}
+
+impl Flag {
+    pub fn new(address: usize, bit: usize) -> Self {
+        Self { address, bit }
+    }
+
+    // switch returns the flag for one of the Great Sea's switches.
+    pub fn switch(switch: u8) -> Self {
+        let switch = switch as usize;
+        // the words are big endian, so the lowest switches are in the last byte.
+        let word = SEA_MEMORY + SWITCHES_OFFSET + (switch / 32) * 4;
+
+        Self::new(word + 3 - (switch % 32) / 8, switch % 8)
+    }
+
+    // read reads the flag. Flags in the sea's saved memory are read from the
+    // current stage memory while Link is at sea, since that's the live copy.
+    pub fn read(&self, d: &Dolphin) -> io::Result<bool> {
+        let mut address = self.address;
+        if (SEA_MEMORY..SEA_MEMORY + STAGE_MEMORY_SIZE).contains(&address)
+            && String::from(stage::StageName::default().read(d)?) == "sea"
+        {
+            address = address - SEA_MEMORY + STAGE_MEMORY;
+        }
+        let byte = d.read_u8(address, None)?;
+
+        Ok(byte.get_bit(self.bit))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PointOfInterest {
+    pub kind: Kind,
+    pub quadrant: world::Quadrant,
+    pub position: (f32, f32),
+    pub cleared: Option<Flag>,
+}
+
+impl fmt::Display for PointOfInterest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) at x: {:.0} z: {:.0}",
+            self.kind, self.quadrant, self.position.0, self.position.1
+        )
+    }
+}
+
+// PointsOfInterest is the dataset of optional points of interest found
+// around the Great Sea, attached to the quadrant each one sits in. Points
+// without a switch can't be tracked, so they are never counted as cleared.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct PointsOfInterest {
+    pub points: Vec<PointOfInterest>,
+}
+
+impl PointsOfInterest {
+    pub fn new(points: Vec<PointOfInterest>) -> Self {
+        Self { points }
+    }
+
+    // from_stage finds the points of interest placed in the sea stage's rooms.
+    // Actors are placed relative to their room, which places them in the world.
+    pub fn from_stage(sea: &dzx::Stage) -> Self {
+        let mut points = Vec::new();
+        for room in &sea.stage.sea_rooms {
+            let (quadrant, dzx) = match (room.quadrant(), sea.room(room.room)) {
+                (Some(quadrant), Some(dzx)) => (quadrant, dzx),
+                _ => continue,
+            };
+
+            for actor in &dzx.actors {
+                if let Some((_, kind, shift)) = POI_ACTORS.iter().find(|(n, ..)| *n == actor.name) {
+                    let switch = (actor.params >> shift) as u8;
+
+                    points.push(PointOfInterest {
+                        kind: *kind,
+                        quadrant,
+                        position: room.to_world(actor.position.x, actor.position.z),
+                        cleared: (switch != NO_SWITCH).then(|| Flag::switch(switch)),
+                    });
+                }
+            }
+        }
+
+        Self { points }
+    }
+
+    pub fn in_quadrant(&self, quadrant: world::Quadrant) -> Vec<&PointOfInterest> {
+        self.points
+            .iter()
+            .filter(|p| p.quadrant == quadrant)
+            .collect()
+    }
+
+    pub fn of_kind(&self, kind: Kind) -> Vec<&PointOfInterest> {
+        self.points.iter().filter(|p| p.kind == kind).collect()
+    }
+
+    // remaining returns the points of interest in the quadrant that have not
+    // yet been cleared.
+    pub fn remaining(
+        &self,
+        quadrant: world::Quadrant,
+        d: &Dolphin,
+    ) -> io::Result<Vec<&PointOfInterest>> {
+        let mut remaining = Vec::new();
+        for point in self.in_quadrant(quadrant) {
+            match point.cleared {
+                Some(flag) if flag.read(d)? => {}
+                _ => remaining.push(point),
+            }
+        }
+
+        Ok(remaining)
+    }
+}
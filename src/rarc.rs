@@ -0,0 +1,613 @@
+use crate::gcm;
+use crate::gcm::{be_u16, be_u32};
+use crate::yaz0;
+use std::io;
+use std::io::{Read, Seek};
+
+// RARC archives hold a tree of directories ("nodes") and the entries inside
+// them. Every offset in the archive is relative to the end of the 0x20 byte
+// header, and file data offsets are relative to the start of the data section.
+pub const MAGIC: &[u8; 4] = b"RARC";
+pub const HEADER_SIZE: usize = 0x20;
+pub const INFO_SIZE: usize = 0x20;
+pub const NODE_SIZE: usize = 0x10;
+pub const ENTRY_SIZE: usize = 0x14;
+
+const ALIGNMENT: usize = 0x20;
+const NO_ID: u16 = 0xFFFF;
+const NO_PARENT: u32 = 0xFFFFFFFF;
+
+// entry flags
+pub const FLAG_FILE: u8 = 0x01;
+pub const FLAG_DIRECTORY: u8 = 0x02;
+pub const FLAG_COMPRESSED: u8 = 0x04;
+pub const FLAG_PRELOAD_MRAM: u8 = 0x10;
+pub const FLAG_PRELOAD_ARAM: u8 = 0x20;
+pub const FLAG_LOAD_DVD: u8 = 0x40;
+pub const FLAG_YAZ0: u8 = 0x80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    pub name: String,
+    pub id: u16,
+    pub flags: u8,
+    pub data: Vec<u8>,
+}
+
+impl File {
+    pub fn new(name: &str, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_owned(),
+            id: 0,
+            flags: FLAG_FILE | FLAG_PRELOAD_MRAM,
+            data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directory {
+    pub name: String,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    File(File),
+    Directory(Directory),
+}
+
+impl Entry {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::File(file) => &file.name,
+            Self::Directory(dir) => &dir.name,
+        }
+    }
+}
+
+impl Directory {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &File> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::File(file) => Some(file),
+            _ => None,
+        })
+    }
+
+    pub fn directories(&self) -> impl Iterator<Item = &Directory> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Directory(dir) => Some(dir),
+            _ => None,
+        })
+    }
+
+    // node_type is the four character id of the directory's node, which is
+    // its name upper-cased and padded or truncated to four characters.
+    fn node_type(&self, is_root: bool) -> [u8; 4] {
+        if is_root {
+            return *b"ROOT";
+        }
+
+        let mut id = [b' '; 4];
+        for (i, b) in self.name.bytes().take(4).enumerate() {
+            id[i] = b.to_ascii_uppercase();
+        }
+
+        id
+    }
+}
+
+// Archive is a parsed RARC archive. Paths within it are relative to the root
+// directory, for example `dzb/room.dzb`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    pub root: Directory,
+    // whether each file's id is its entry index, which is how most of the
+    // game's archives are built.
+    pub sync_ids: bool,
+}
+
+impl Archive {
+    pub fn new(name: &str) -> Self {
+        Self {
+            root: Directory::new(name),
+            sync_ids: true,
+        }
+    }
+
+    // from_disc reads, decompresses and parses an archive from a disc image.
+    pub fn from_disc<R: Read + Seek>(disc: &mut gcm::Disc<R>, path: &str) -> io::Result<Self> {
+        Self::parse(&disc.read_file(path)?)
+    }
+
+    // parse reads an archive, decompressing it first if it is Yaz0 compressed.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if yaz0::is_compressed(data) {
+            return Self::parse(&yaz0::decompress(data)?);
+        }
+
+        if data.len() < HEADER_SIZE + INFO_SIZE || &data[0..4] != MAGIC {
+            return Err(gcm::invalid_data("not a RARC archive"));
+        }
+
+        let data_offset = HEADER_SIZE + be_u32(data, 0xC)? as usize;
+        let info = HEADER_SIZE;
+        let node_count = be_u32(data, info)? as usize;
+        let nodes = HEADER_SIZE + be_u32(data, info + 0x4)? as usize;
+        let entries = HEADER_SIZE + be_u32(data, info + 0xC)? as usize;
+        let strings = HEADER_SIZE + be_u32(data, info + 0x14)? as usize;
+        let sync_ids = *data.get(info + 0x1A).unwrap_or(&0) != 0;
+
+        if node_count == 0 {
+            return Err(gcm::invalid_data("RARC archive has no root node"));
+        }
+
+        let reader = Reader {
+            data,
+            nodes,
+            entries,
+            strings,
+            data_offset,
+            node_count,
+        };
+        let root = reader.directory(0, 0)?;
+
+        Ok(Self { root, sync_ids })
+    }
+
+    pub fn get(&self, path: &str) -> Option<&File> {
+        let (dirs, name) = split_path(path);
+        let mut dir = &self.root;
+        for part in dirs {
+            dir = dir.directories().find(|d| d.name == part)?;
+        }
+
+        dir.files().find(|f| f.name == name)
+    }
+
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut File> {
+        let (dirs, name) = split_path(path);
+        let mut dir = &mut self.root;
+        for part in dirs {
+            dir = dir.entries.iter_mut().find_map(|e| match e {
+                Entry::Directory(d) if d.name == part => Some(d),
+                _ => None,
+            })?;
+        }
+
+        dir.entries.iter_mut().find_map(|e| match e {
+            Entry::File(f) if f.name == name => Some(f),
+            _ => None,
+        })
+    }
+
+    // insert replaces the file at the path, or adds it along with any missing
+    // directories if it doesn't exist.
+    pub fn insert(&mut self, path: &str, file: File) {
+        let (dirs, _) = split_path(path);
+        let mut dir = &mut self.root;
+        for part in dirs {
+            let index = match dir
+                .entries
+                .iter()
+                .position(|e| matches!(e, Entry::Directory(d) if d.name == part))
+            {
+                Some(index) => index,
+                None => {
+                    dir.entries.push(Entry::Directory(Directory::new(part)));
+                    dir.entries.len() - 1
+                }
+            };
+
+            dir = match &mut dir.entries[index] {
+                Entry::Directory(d) => d,
+                Entry::File(_) => unreachable!(),
+            };
+        }
+
+        match dir
+            .entries
+            .iter_mut()
+            .find(|e| matches!(e, Entry::File(f) if f.name == file.name))
+        {
+            Some(entry) => *entry = Entry::File(file),
+            None => dir.entries.push(Entry::File(file)),
+        }
+    }
+
+    // files lists every file in the archive along with its path.
+    pub fn files(&self) -> Vec<(String, &File)> {
+        let mut files = Vec::new();
+        collect_files(&self.root, "", &mut files);
+
+        files
+    }
+
+    // to_bytes writes the archive back out, uncompressed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        Writer::default().write(self)
+    }
+
+    pub fn to_yaz0(&self, level: yaz0::Level) -> Vec<u8> {
+        yaz0::compress(&self.to_bytes(), level)
+    }
+}
+
+fn split_path(path: &str) -> (Vec<&str>, &str) {
+    let mut parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    let name = parts.pop().unwrap_or("");
+
+    (parts, name)
+}
+
+fn collect_files<'a>(dir: &'a Directory, prefix: &str, files: &mut Vec<(String, &'a File)>) {
+    for entry in &dir.entries {
+        match entry {
+            Entry::File(file) => files.push((format!("{}{}", prefix, file.name), file)),
+            Entry::Directory(d) => collect_files(d, &format!("{}{}/", prefix, d.name), files),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    nodes: usize,
+    entries: usize,
+    strings: usize,
+    data_offset: usize,
+    node_count: usize,
+}
+
+impl Reader<'_> {
+    // directory reads a node and everything beneath it. depth guards against
+    // archives whose directories refer back to themselves.
+    fn directory(&self, index: usize, depth: usize) -> io::Result<Directory> {
+        if index >= self.node_count || depth > self.node_count {
+            return Err(gcm::invalid_data("RARC node is out of range"));
+        }
+
+        let node = self.nodes + index * NODE_SIZE;
+        let name = self.string(be_u32(self.data, node + 0x4)? as usize)?;
+        let count = be_u16(self.data, node + 0xA)? as usize;
+        let first = be_u32(self.data, node + 0xC)? as usize;
+
+        let mut dir = Directory::new(&name);
+        for i in first..first + count {
+            let entry = self.entries + i * ENTRY_SIZE;
+            let id = be_u16(self.data, entry)?;
+            let flags = *self
+                .data
+                .get(entry + 0x4)
+                .ok_or_else(|| gcm::invalid_data("RARC entry is out of range"))?;
+            let name = self.string(be_u16(self.data, entry + 0x6)? as usize)?;
+            let offset = be_u32(self.data, entry + 0x8)? as usize;
+            let size = be_u32(self.data, entry + 0xC)? as usize;
+
+            if flags & FLAG_DIRECTORY != 0 {
+                if name == "." || name == ".." {
+                    continue;
+                }
+
+                dir.entries
+                    .push(Entry::Directory(self.directory(offset, depth + 1)?));
+            } else {
+                let start = self.data_offset + offset;
+                let data = self
+                    .data
+                    .get(start..start + size)
+                    .ok_or_else(|| gcm::invalid_data("RARC file data is out of range"))?;
+
+                dir.entries.push(Entry::File(File {
+                    name,
+                    id,
+                    flags,
+                    data: data.to_vec(),
+                }));
+            }
+        }
+
+        Ok(dir)
+    }
+
+    fn string(&self, offset: usize) -> io::Result<String> {
+        self.data
+            .get(self.strings + offset..)
+            .map(gcm::c_string)
+            .ok_or_else(|| gcm::invalid_data("RARC name is out of range"))
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    nodes: Vec<u8>,
+    entries: Vec<[u8; ENTRY_SIZE]>,
+    strings: Vec<u8>,
+    // (entry index, flags, data) for every file, in entry order.
+    files: Vec<(usize, u8, Vec<u8>)>,
+    node_count: usize,
+}
+
+impl Writer {
+    fn write(mut self, archive: &Archive) -> Vec<u8> {
+        self.string(".");
+        self.string("..");
+
+        // nodes are numbered depth first, and each node's entries are kept
+        // together, so size the node table up front and fill it in as we go.
+        let node_count = count_nodes(&archive.root);
+        self.nodes = vec![0u8; node_count * NODE_SIZE];
+        self.node_count = 1;
+        self.directory(&archive.root, 0, NO_PARENT, archive.sync_ids);
+
+        // files preloaded to main memory come first, then those for ARAM,
+        // then anything left to be read from the disc when needed.
+        let mut data = Vec::new();
+        let mut sizes = [0u32; 3];
+        for (group, size) in sizes.iter_mut().enumerate() {
+            let start = data.len();
+            for (entry, flags, file) in &self.files {
+                if load_group(*flags) != group {
+                    continue;
+                }
+
+                self.entries[*entry][0x8..0xC].copy_from_slice(&(data.len() as u32).to_be_bytes());
+                self.entries[*entry][0xC..0x10].copy_from_slice(&(file.len() as u32).to_be_bytes());
+                data.extend_from_slice(file);
+                pad(&mut data);
+            }
+            *size = (data.len() - start) as u32;
+        }
+
+        let nodes_offset = INFO_SIZE;
+        let entries_offset = align(nodes_offset + self.nodes.len());
+        let strings_offset = align(entries_offset + self.entries.len() * ENTRY_SIZE);
+        pad(&mut self.strings);
+        let data_offset = align(strings_offset + self.strings.len());
+
+        let next_id = if archive.sync_ids {
+            self.entries.len() as u16
+        } else {
+            self.files
+                .iter()
+                .map(|(entry, _, _)| be_u16(&self.entries[*entry], 0).unwrap_or(0))
+                .max()
+                .map_or(0, |id| id + 1)
+        };
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&((HEADER_SIZE + data_offset + data.len()) as u32).to_be_bytes());
+        out.extend_from_slice(&(HEADER_SIZE as u32).to_be_bytes());
+        out.extend_from_slice(&(data_offset as u32).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        for size in sizes {
+            out.extend_from_slice(&size.to_be_bytes());
+        }
+
+        out.extend_from_slice(&(node_count as u32).to_be_bytes());
+        out.extend_from_slice(&(nodes_offset as u32).to_be_bytes());
+        out.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        out.extend_from_slice(&(entries_offset as u32).to_be_bytes());
+        out.extend_from_slice(&(self.strings.len() as u32).to_be_bytes());
+        out.extend_from_slice(&(strings_offset as u32).to_be_bytes());
+        out.extend_from_slice(&next_id.to_be_bytes());
+        out.push(archive.sync_ids as u8);
+        out.resize(HEADER_SIZE + INFO_SIZE, 0);
+
+        out.extend_from_slice(&self.nodes);
+        out.resize(HEADER_SIZE + entries_offset, 0);
+        out.extend_from_slice(&self.entries.concat());
+        out.resize(HEADER_SIZE + strings_offset, 0);
+        out.extend_from_slice(&self.strings);
+        out.resize(HEADER_SIZE + data_offset, 0);
+        out.extend_from_slice(&data);
+
+        out
+    }
+
+    fn directory(&mut self, dir: &Directory, index: usize, parent: u32, sync_ids: bool) {
+        let name = self.string(&dir.name);
+        let first = self.entries.len();
+        let count = dir.entries.len() + 2;
+
+        let node = &mut self.nodes[index * NODE_SIZE..(index + 1) * NODE_SIZE];
+        node[0x0..0x4].copy_from_slice(&dir.node_type(index == 0));
+        node[0x4..0x8].copy_from_slice(&(name as u32).to_be_bytes());
+        node[0x8..0xA].copy_from_slice(&hash(&dir.name).to_be_bytes());
+        node[0xA..0xC].copy_from_slice(&(count as u16).to_be_bytes());
+        node[0xC..0x10].copy_from_slice(&(first as u32).to_be_bytes());
+
+        // reserve this node's entries so that they stay contiguous.
+        self.entries.resize(first + count, [0u8; ENTRY_SIZE]);
+
+        for (i, entry) in dir.entries.iter().enumerate() {
+            let slot = first + i;
+            match entry {
+                Entry::File(file) => {
+                    let id = if sync_ids { slot as u16 } else { file.id };
+                    let name = self.string(&file.name);
+                    self.entries[slot] = entry_bytes(id, &file.name, file.flags, name, 0, 0);
+                    self.files.push((slot, file.flags, file.data.clone()));
+                }
+                Entry::Directory(child) => {
+                    // number each directory as it is reached, so its children
+                    // follow it before its next sibling.
+                    let child_index = self.node_count;
+                    self.node_count += 1;
+                    let name = self.string(&child.name);
+                    self.entries[slot] = entry_bytes(
+                        NO_ID,
+                        &child.name,
+                        FLAG_DIRECTORY,
+                        name,
+                        child_index as u32,
+                        NODE_SIZE as u32,
+                    );
+                    self.directory(child, child_index, index as u32, sync_ids);
+                }
+            }
+        }
+
+        self.entries[first + count - 2] = entry_bytes(
+            NO_ID,
+            ".",
+            FLAG_DIRECTORY,
+            0,
+            index as u32,
+            NODE_SIZE as u32,
+        );
+        self.entries[first + count - 1] =
+            entry_bytes(NO_ID, "..", FLAG_DIRECTORY, 2, parent, NODE_SIZE as u32);
+    }
+
+    // string adds a name to the string table, returning its offset.
+    fn string(&mut self, name: &str) -> usize {
+        let offset = self.strings.len();
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+
+        offset
+    }
+}
+
+fn entry_bytes(
+    id: u16,
+    name: &str,
+    flags: u8,
+    name_offset: usize,
+    offset: u32,
+    size: u32,
+) -> [u8; ENTRY_SIZE] {
+    let mut entry = [0u8; ENTRY_SIZE];
+    entry[0x0..0x2].copy_from_slice(&id.to_be_bytes());
+    entry[0x2..0x4].copy_from_slice(&hash(name).to_be_bytes());
+    entry[0x4] = flags;
+    entry[0x6..0x8].copy_from_slice(&(name_offset as u16).to_be_bytes());
+    entry[0x8..0xC].copy_from_slice(&offset.to_be_bytes());
+    entry[0xC..0x10].copy_from_slice(&size.to_be_bytes());
+
+    entry
+}
+
+// load_group is the section of the data a file is stored in: 0 for main
+// memory, 1 for ARAM and 2 for files read from the disc on demand.
+fn load_group(flags: u8) -> usize {
+    if flags & FLAG_PRELOAD_MRAM != 0 {
+        0
+    } else if flags & FLAG_PRELOAD_ARAM != 0 {
+        1
+    } else {
+        2
+    }
+}
+
+fn count_nodes(dir: &Directory) -> usize {
+    1 + dir.directories().map(count_nodes).sum::<usize>()
+}
+
+// hash is the name hash the game uses to look entries up quickly.
+pub fn hash(name: &str) -> u16 {
+    name.bytes()
+        .fold(0u16, |h, b| h.wrapping_mul(3).wrapping_add(b as u16))
+}
+
+fn align(n: usize) -> usize {
+    (n + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize(align(buf.len()), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(sync_ids: bool) -> Archive {
+        let mut archive = Archive::new("root");
+        archive.sync_ids = sync_ids;
+
+        let paths = [
+            "top.bin",
+            "a/x.bin",
+            "a/b/c.bin",
+            "a/b/d/e.bin",
+            "a/b/f.bin",
+            "b/y.bin",
+            "b/c/z.bin",
+            "empty/dir/g.bin",
+        ];
+        for (i, path) in paths.iter().enumerate() {
+            let (_, name) = split_path(path);
+            let mut file = File::new(name, vec![i as u8; i * 7 + 1]);
+            file.id = 100 + i as u16;
+            if i % 3 == 1 {
+                file.flags = FLAG_FILE | FLAG_PRELOAD_ARAM;
+            }
+            archive.insert(path, file);
+        }
+
+        archive
+    }
+
+    fn paths(archive: &Archive) -> Vec<(String, Vec<u8>, u8)> {
+        archive
+            .files()
+            .into_iter()
+            .map(|(path, f)| (path, f.data.clone(), f.flags))
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_keeps_ids() {
+        let archive = archive(false);
+        let parsed = Archive::parse(&archive.to_bytes()).unwrap();
+
+        assert_eq!(parsed, archive);
+    }
+
+    #[test]
+    fn round_trip_syncs_ids() {
+        let archive = archive(true);
+        let bytes = archive.to_bytes();
+        let parsed = Archive::parse(&bytes).unwrap();
+
+        assert!(parsed.sync_ids);
+        assert_eq!(paths(&parsed), paths(&archive));
+        assert_eq!(parsed.to_bytes(), bytes);
+        for (_, file) in parsed.files() {
+            assert!(file.id < 100);
+        }
+    }
+
+    #[test]
+    fn round_trip_nested() {
+        let mut archive = Archive::new("root");
+        archive.insert("a/b/c.bin", File::new("c.bin", b"nested".to_vec()));
+        let parsed = Archive::parse(&archive.to_bytes()).unwrap();
+
+        assert_eq!(parsed.get("a/b/c.bin").unwrap().data, b"nested");
+    }
+
+    #[test]
+    fn round_trip_yaz0() {
+        let archive = archive(false);
+        let parsed = Archive::parse(&archive.to_yaz0(yaz0::Level::fast())).unwrap();
+
+        assert_eq!(parsed, archive);
+    }
+
+    #[test]
+    fn rejects_truncated() {
+        let bytes = archive(true).to_bytes();
+
+        assert!(Archive::parse(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
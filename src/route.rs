@@ -0,0 +1,192 @@
+use crate::korl;
+use crate::player;
+use crate::world;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+// the game runs at 30 frames per second, and korl::Speed is measured in units per frame.
+const FRAMES_PER_SECOND: f32 = 30.0;
+// roughly how long the Ballad of Gales cyclone takes from playing the song to landing.
+const WARP_DURATION: Duration = Duration::from_secs(12);
+
+// BALLAD_OF_GALES_WARPS are the quadrants the Ballad of Gales cyclone can drop
+// Link at once their warp point has been unlocked.
+pub const BALLAD_OF_GALES_WARPS: [world::Quadrant; 10] = [
+    world::Quadrant::WindfallIsland,
+    world::Quadrant::DragonRoostIsland,
+    world::Quadrant::ForestHaven,
+    world::Quadrant::OutsetIsland,
+    world::Quadrant::TowerOfTheGods,
+    world::Quadrant::GreatfishIsle,
+    world::Quadrant::MotherAndChildIsles,
+    world::Quadrant::NorthernFairyIsland,
+    world::Quadrant::SouthernFairyIsland,
+    world::Quadrant::WesternFairyIsland,
+];
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum Leg {
+    Warp {
+        to: world::Quadrant,
+    },
+    Sail {
+        to: world::Quadrant,
+        bearing: f32,
+        heading: world::Compass,
+        distance: f32,
+    },
+}
+
+impl fmt::Display for Leg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Warp { to } => write!(f, "Warp to {}", to),
+            Self::Sail {
+                to,
+                bearing,
+                heading,
+                distance,
+            } => write!(
+                f,
+                "Sail {} ({:.0}°) for {:.0} units to {}",
+                heading, bearing, distance, to
+            ),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Route {
+    pub legs: Vec<Leg>,
+    pub distance: f32,
+    pub estimate: Option<Duration>,
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, leg) in self.legs.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", then ")?;
+            }
+            write!(f, "{}", leg)?;
+        }
+
+        if let Some(estimate) = self.estimate {
+            write!(f, " (~{:.0}s)", estimate.as_secs_f32())?;
+        }
+
+        Ok(())
+    }
+}
+
+// RoutePlanner plans routes across a map using straight-line sailing and,
+// once unlocked, the Ballad of Gales. Islands the map has no position for
+// fall back to the centre of their square.
+#[derive(Debug, Clone)]
+pub struct RoutePlanner {
+    map: world::Map,
+    warps: Vec<world::Quadrant>,
+    speed: f32,
+}
+
+impl RoutePlanner {
+    // new returns a planner for the Great Sea with its islands at the given
+    // positions.
+    pub fn new(islands: HashMap<world::Quadrant, (f32, f32)>, speed: korl::Speed) -> Self {
+        Self::with_map(world::Map::with_islands(islands), speed)
+    }
+
+    pub fn with_map(map: world::Map, speed: korl::Speed) -> Self {
+        Self {
+            map,
+            warps: Vec::new(),
+            speed: speed.into(),
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: korl::Speed) {
+        self.speed = speed.into();
+    }
+
+    pub fn unlock_warps(&mut self, warps: &[world::Quadrant]) {
+        for warp in warps {
+            if BALLAD_OF_GALES_WARPS.contains(warp) && !self.warps.contains(warp) {
+                self.warps.push(*warp);
+            }
+        }
+    }
+
+    pub fn island(&self, quadrant: world::Quadrant) -> Option<(f32, f32)> {
+        self.map.island_position(quadrant)
+    }
+
+    // sail returns the straight-line leg from a position to an island.
+    pub fn sail(&self, from: (f32, f32), to: world::Quadrant) -> Option<Leg> {
+        let target = self.island(to)?;
+        let (dx, dz) = (target.0 - from.0, target.1 - from.1);
+        let bearing = world::bearing(from, target);
+
+        Some(Leg::Sail {
+            to,
+            bearing,
+            heading: world::Compass::new(bearing),
+            distance: (dx * dx + dz * dz).sqrt(),
+        })
+    }
+
+    // plan returns the fastest route from a position to an island, either by
+    // sailing directly or by warping to an unlocked Ballad of Gales point first.
+    pub fn plan(&self, from: &player::Position, to: world::Quadrant) -> Option<Route> {
+        let direct = self.route(vec![self.sail((from.x, from.z), to)?]);
+
+        let warped = self.warps.iter().filter_map(|warp| {
+            let mut legs = vec![Leg::Warp { to: *warp }];
+            if *warp != to {
+                legs.push(self.sail(self.island(*warp)?, to)?);
+            }
+
+            Some(self.route(legs))
+        });
+
+        std::iter::once(direct)
+            .chain(warped)
+            .min_by(|a, b| match (a.estimate, b.estimate) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a.distance.total_cmp(&b.distance),
+            })
+    }
+
+    fn route(&self, legs: Vec<Leg>) -> Route {
+        let mut route = Route {
+            legs,
+            distance: 0.0,
+            estimate: Some(Duration::ZERO),
+        };
+
+        for leg in &route.legs {
+            let time = match *leg {
+                Leg::Warp { .. } => Some(WARP_DURATION),
+                Leg::Sail { distance, .. } => {
+                    route.distance += distance;
+                    self.sail_time(distance)
+                }
+            };
+
+            route.estimate = route.estimate.zip(time).map(|(a, b)| a + b);
+        }
+
+        route
+    }
+
+    fn sail_time(&self, distance: f32) -> Option<Duration> {
+        if self.speed <= 0.0 {
+            return None;
+        }
+
+        Some(Duration::from_secs_f32(
+            distance / (self.speed * FRAMES_PER_SECOND),
+        ))
+    }
+}
@@ -0,0 +1,319 @@
+use crate::stage;
+use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::mem;
+
+// 803C9D54,1 - Non-zero while a change to the next stage has been requested
+// and the game has not yet started loading it.
+pub const NEXT_STAGE_ENABLED: usize = 0x803C9D54;
+// 803C9D55,1 - The wipe (fade) type used for the requested stage change.
+pub const NEXT_STAGE_WIPE: usize = 0x803C9D55;
+// 803D3A44,4 - Screen fade progress. Float from 0 (fully visible) to 1 (fully black).
+pub const FADE_PROGRESS: usize = 0x803D3A44;
+// 803CA7B5,1 - Non-zero while an event such as a cutscene or dialogue is running.
+pub const EVENT_RUNNING: usize = 0x803CA7B5;
+// 803E9D34,4 - The number of frames the game has run for, which keeps
+// counting through pauses and loads.
+pub const FRAME_COUNTER: usize = 0x803E9D34;
+
+// SceneChange is a snapshot of everything the game uses to move the player
+// between stages, taken on the game frame `frame`. Snapshots are fed into a
+// `LoadTracker`, ideally once per frame.
+#[derive(Default, Debug, Clone)]
+pub struct SceneChange {
+    pub stage: String,
+    pub next_stage: String,
+    pub requested: bool,
+    pub fade: f32,
+    pub frame: u32,
+}
+
+impl SceneChange {
+    pub fn new(stage: &str, next_stage: &str, requested: bool, fade: f32, frame: u32) -> Self {
+        Self {
+            stage: stage.to_owned(),
+            next_stage: next_stage.to_owned(),
+            requested,
+            fade,
+            frame,
+        }
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.stage = stage::StageName::default().read(d)?.into();
+        self.next_stage = stage::NextStageName::default().read(d)?.into();
+        self.requested = d.read_u8(NEXT_STAGE_ENABLED, None)? != 0;
+        self.fade = d.read_f32(FADE_PROGRESS, None)?;
+        self.frame = d.read_u32(FRAME_COUNTER, None)?;
+
+        Ok(self.clone())
+    }
+
+    fn is_black(&self) -> bool {
+        self.fade >= 1.0
+    }
+
+    fn is_clear(&self) -> bool {
+        self.fade <= 0.0
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadState {
+    #[default]
+    Playing,
+    FadingOut {
+        from: String,
+        to: String,
+    },
+    Loading {
+        from: String,
+        to: String,
+    },
+    FadingIn {
+        from: String,
+        to: String,
+    },
+}
+
+impl LoadState {
+    // is_loading returns true only while the screen is black and the next
+    // stage is being loaded, which is the time removed by load-removed timers.
+    pub fn is_loading(&self) -> bool {
+        matches!(self, Self::Loading { .. })
+    }
+}
+
+impl fmt::Display for LoadState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Playing => write!(f, "Playing"),
+            Self::FadingOut { from, to } => write!(f, "Fading out ({} -> {})", from, to),
+            Self::Loading { from, to } => write!(f, "Loading ({} -> {})", from, to),
+            Self::FadingIn { from, to } => write!(f, "Fading in ({} -> {})", from, to),
+        }
+    }
+}
+
+// Transition records the game frame of the snapshot on which the tracker
+// moved between two states.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Transition {
+    pub from: LoadState,
+    pub to: LoadState,
+    pub frame: u32,
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {} -> {}", self.frame, self.from, self.to)
+    }
+}
+
+// LoadTracker is a state machine over `SceneChange` snapshots. Transitions are
+// stamped with the game's own frame counter rather than a count of updates, so
+// they stay accurate however often the tracker is polled. Polling less than
+// once per frame only delays when a transition is seen, not the frame reported
+// for the snapshot it was seen on.
+#[derive(Default, Debug, Clone)]
+pub struct LoadTracker {
+    state: LoadState,
+    frame: u32,
+}
+
+impl LoadTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self) -> &LoadState {
+        &self.state
+    }
+
+    // frame returns the game frame of the last snapshot.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Option<Transition>> {
+        let scene = SceneChange::default().read(d)?;
+
+        Ok(self.update(&scene))
+    }
+
+    pub fn update(&mut self, scene: &SceneChange) -> Option<Transition> {
+        self.frame = scene.frame;
+
+        let next = match &self.state {
+            LoadState::Playing if scene.requested => Some(LoadState::FadingOut {
+                from: scene.stage.clone(),
+                to: scene.next_stage.clone(),
+            }),
+            LoadState::FadingOut { from, to } if scene.is_black() => Some(LoadState::Loading {
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            // wipes that don't fade to black, or a fade that peaks just short
+            // of it, never reach black, so the game taking the request or
+            // swapping stages also means the load has started.
+            LoadState::FadingOut { from, to } if !scene.requested || scene.stage != *from => {
+                Some(LoadState::Loading {
+                    from: from.clone(),
+                    to: to.clone(),
+                })
+            }
+            // the request flag is cleared once the game has swapped over to the
+            // next stage, so the screen only starts clearing after the load.
+            LoadState::Loading { from, to } if !scene.requested && !scene.is_black() => {
+                Some(LoadState::FadingIn {
+                    from: from.clone(),
+                    to: to.clone(),
+                })
+            }
+            // a new stage change can be requested before the fade in completes,
+            // such as when voiding out immediately after entering a room.
+            LoadState::FadingIn { .. } if scene.requested => Some(LoadState::FadingOut {
+                from: scene.stage.clone(),
+                to: scene.next_stage.clone(),
+            }),
+            LoadState::FadingIn { .. } if scene.is_clear() => Some(LoadState::Playing),
+            _ => None,
+        }?;
+
+        let from = mem::replace(&mut self.state, next);
+
+        Some(Transition {
+            from,
+            to: self.state.clone(),
+            frame: self.frame,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fading_out() -> LoadState {
+        LoadState::FadingOut {
+            from: "sea".to_owned(),
+            to: "M_NewD2".to_owned(),
+        }
+    }
+
+    fn loading() -> LoadState {
+        LoadState::Loading {
+            from: "sea".to_owned(),
+            to: "M_NewD2".to_owned(),
+        }
+    }
+
+    fn fading_in() -> LoadState {
+        LoadState::FadingIn {
+            from: "sea".to_owned(),
+            to: "M_NewD2".to_owned(),
+        }
+    }
+
+    // step feeds a snapshot to the tracker and returns the state it moved to.
+    fn step(
+        tracker: &mut LoadTracker,
+        stage: &str,
+        requested: bool,
+        fade: f32,
+        frame: u32,
+    ) -> Option<LoadState> {
+        let scene = SceneChange::new(stage, "M_NewD2", requested, fade, frame);
+
+        tracker.update(&scene).map(|t| {
+            assert_eq!(t.frame, frame);
+            t.to
+        })
+    }
+
+    #[test]
+    fn fade_to_black() {
+        let mut tracker = LoadTracker::new();
+
+        assert_eq!(step(&mut tracker, "sea", false, 0.0, 1), None);
+        assert_eq!(step(&mut tracker, "sea", true, 0.0, 2), Some(fading_out()));
+        assert_eq!(step(&mut tracker, "sea", true, 0.5, 3), None);
+        assert_eq!(step(&mut tracker, "sea", true, 1.0, 4), Some(loading()));
+        assert_eq!(step(&mut tracker, "M_NewD2", true, 1.0, 5), None);
+        assert_eq!(step(&mut tracker, "M_NewD2", false, 1.0, 6), None);
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", false, 0.9, 7),
+            Some(fading_in())
+        );
+        assert_eq!(step(&mut tracker, "M_NewD2", false, 0.4, 8), None);
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", false, 0.0, 9),
+            Some(LoadState::Playing)
+        );
+        assert_eq!(tracker.frame(), 9);
+        assert!(!tracker.state().is_loading());
+    }
+
+    #[test]
+    fn wipe_without_black() {
+        let mut tracker = LoadTracker::new();
+
+        assert_eq!(step(&mut tracker, "sea", true, 0.0, 1), Some(fading_out()));
+        assert_eq!(step(&mut tracker, "sea", true, 0.99, 2), None);
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", true, 0.99, 3),
+            Some(loading())
+        );
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", false, 0.5, 4),
+            Some(fading_in())
+        );
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", false, 0.0, 5),
+            Some(LoadState::Playing)
+        );
+    }
+
+    #[test]
+    fn request_taken_without_black() {
+        let mut tracker = LoadTracker::new();
+
+        assert_eq!(step(&mut tracker, "sea", true, 0.0, 1), Some(fading_out()));
+        assert_eq!(step(&mut tracker, "sea", false, 0.0, 2), Some(loading()));
+        assert_eq!(step(&mut tracker, "sea", false, 0.0, 3), Some(fading_in()));
+        assert_eq!(
+            step(&mut tracker, "sea", false, 0.0, 4),
+            Some(LoadState::Playing)
+        );
+    }
+
+    #[test]
+    fn void_out_while_fading_in() {
+        let mut tracker = LoadTracker::new();
+
+        step(&mut tracker, "sea", true, 0.0, 1);
+        step(&mut tracker, "sea", true, 1.0, 2);
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", false, 0.8, 3),
+            Some(fading_in())
+        );
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", true, 0.6, 4),
+            Some(LoadState::FadingOut {
+                from: "M_NewD2".to_owned(),
+                to: "M_NewD2".to_owned(),
+            })
+        );
+        assert_eq!(
+            step(&mut tracker, "M_NewD2", true, 1.0, 5),
+            Some(LoadState::Loading {
+                from: "M_NewD2".to_owned(),
+                to: "M_NewD2".to_owned(),
+            })
+        );
+        assert!(tracker.state().is_loading());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::scene;
 use dolphin_memory::Dolphin;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -12,8 +13,52 @@ pub const STAGE_ID: usize = 0x803C53A4;
 
 // 803C9D3C,8 - The current stage name.
 pub const STAGE_NAME: usize = 0x803C9D3C;
+// 803C9D44,2 - The spawn (entrance) id the player entered the current stage from.
+pub const STAGE_SPAWN: usize = 0x803C9D44;
+// 803C9D46,1 - The room number the player is currently in.
+pub const STAGE_ROOM: usize = 0x803C9D46;
+// 803C9D47,1 - The layer the current stage was loaded with.
+pub const STAGE_LAYER: usize = 0x803C9D47;
+
 // 803C9D48,8 - The next stage name the player is about to go to.
 pub const NEXT_STAGE_NAME: usize = 0x803C9D48;
+// 803C9D50,2 - The spawn id the player will enter the next stage from.
+pub const NEXT_STAGE_SPAWN: usize = 0x803C9D50;
+// 803C9D52,1 - The room number the player will load into.
+pub const NEXT_STAGE_ROOM: usize = 0x803C9D52;
+// 803C9D53,1 - The layer the next stage will be loaded with.
+pub const NEXT_STAGE_LAYER: usize = 0x803C9D53;
+
+// rooms are numbered 0-63, and layers 0-11 with -1 letting the game pick the
+// layer for the current time and story progress.
+pub const MAX_ROOM: i8 = 63;
+pub const MAX_LAYER: i8 = 11;
+// DEFAULT_WIPE is the plain fade to black used by most loading zones.
+pub const DEFAULT_WIPE: u8 = 0;
+
+// STAGE_NAMES is the catalogue of stage names the game is able to load.
+// Writing any other name as the next stage will crash the game.
+pub const STAGE_NAMES: [&str; 121] = [
+    "sea", "sea_T", "Asoko", "Abesso", "A_umikz", "ADMumi", "Atorizk", "Adanmae", "M_NewD2",
+    "M_DragB", "Pdrgsh", "Omori", "Otkura", "kindan", "kinBOSS", "Siren", "SirenB", "Edaichi",
+    "M_Dai", "M_DaiB", "Ekaze", "kaze", "kazeB", "MajyuE", "majroom", "ma2room", "ma3room",
+    "Mjtower", "M2tower", "M2ganon", "GanonA", "GanonB", "GanonC", "GanonD", "GanonE", "GanonJ",
+    "GanonK", "GanonL", "GanonM", "GanonN", "GTower", "Xboss0", "Xboss1", "Xboss2", "Xboss3",
+    "Hyrule", "Hyroom", "kenroom", "LinkRM", "LinkUG", "Ojhous", "Ojhous2", "Orichh", "Omasao",
+    "Onobuta", "Opub", "Obombh", "Ocmera", "Ocrogh", "Comori", "Kaisen", "Nitiyou", "Obshop",
+    "Pnezumi", "Pfigure", "figureA", "figureB", "figureC", "figureD", "figureE", "figureF",
+    "figureG", "PShip", "PShip2", "PShip3", "Pjavdou", "ShipD", "Cave01", "Cave02", "Cave03",
+    "Cave04", "Cave05", "Cave06", "Cave07", "Cave08", "Cave09", "Cave10", "Cave11", "MiniKaz",
+    "MiniHyo", "TyuTyu", "SubD42", "SubD43", "SubD44", "SubD51", "SubD71", "TF_01", "TF_02",
+    "TF_03", "TF_04", "TF_06", "TF_07", "WarpD", "Ebesso", "A_nami", "ITest61", "ITest62",
+    "ITest63", "K_Test2", "K_Test3", "K_Test4", "K_Test5", "K_Test6", "K_Test8", "K_Test9",
+    "K_Testa", "K_Testb", "K_Testc", "K_Testd", "K_Teste", "E3ROOP",
+];
+
+// is_known_stage returns whether the given name is in the stage catalogue.
+pub fn is_known_stage(name: &str) -> bool {
+    STAGE_NAMES.contains(&name)
+}
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
@@ -178,3 +223,110 @@ impl fmt::Display for NextStageName {
         write!(f, "{}", self.0)
     }
 }
+
+// StageInfo is the full location of the player within a stage. Reading it
+// with `read` gives the current stage, while `read_next` gives the stage the
+// player is about to be sent to, which together describe a transition.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StageInfo {
+    pub name: String,
+    pub room: i8,
+    pub spawn: i16,
+    pub layer: i8,
+}
+
+impl StageInfo {
+    pub fn new(name: &str, room: i8, spawn: i16, layer: i8) -> Self {
+        Self {
+            name: name.to_owned(),
+            room,
+            spawn,
+            layer,
+        }
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.name = d
+            .read_string(8, STAGE_NAME, None)?
+            .trim_matches(char::from(0))
+            .to_owned();
+        self.room = d.read_i8(STAGE_ROOM, None)?;
+        self.spawn = d.read_i16(STAGE_SPAWN, None)?;
+        self.layer = d.read_i8(STAGE_LAYER, None)?;
+
+        Ok(self.clone())
+    }
+
+    pub fn read_next(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.name = d
+            .read_string(8, NEXT_STAGE_NAME, None)?
+            .trim_matches(char::from(0))
+            .to_owned();
+        self.room = d.read_i8(NEXT_STAGE_ROOM, None)?;
+        self.spawn = d.read_i16(NEXT_STAGE_SPAWN, None)?;
+        self.layer = d.read_i8(NEXT_STAGE_LAYER, None)?;
+
+        Ok(self.clone())
+    }
+
+    // write_next requests a warp to this stage by writing it as the next stage
+    // and setting the scene change trigger. The game performs the transition
+    // itself, so this refuses to act while an event or another load is running.
+    pub fn write_next(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.validate()?;
+
+        let scene = scene::SceneChange::default().read(d)?;
+        if scene.requested || scene.fade > 0.0 {
+            return Err(io::Error::other("a stage change is already in progress"));
+        }
+
+        if d.read_u8(scene::EVENT_RUNNING, None)? != 0 {
+            return Err(io::Error::other("cannot warp while an event is running"));
+        }
+
+        let mut name = [0u8; 8];
+        name[..self.name.len()].copy_from_slice(self.name.as_bytes());
+
+        d.write(&name, NEXT_STAGE_NAME, None)?;
+        d.write_u8(self.room as u8, NEXT_STAGE_ROOM, None)?;
+        d.write(&self.spawn.to_be_bytes(), NEXT_STAGE_SPAWN, None)?;
+        d.write_u8(self.layer as u8, NEXT_STAGE_LAYER, None)?;
+        d.write_u8(DEFAULT_WIPE, scene::NEXT_STAGE_WIPE, None)?;
+        d.write_u8(1, scene::NEXT_STAGE_ENABLED, None)?;
+
+        Ok(self.clone())
+    }
+
+    // validate checks that the game can load this location.
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+
+        if !is_known_stage(&self.name) {
+            return invalid(format!("unknown stage name: {}", self.name));
+        }
+
+        if !(0..=MAX_ROOM).contains(&self.room) {
+            return invalid(format!("room {} is out of range", self.room));
+        }
+
+        if !(-1..=MAX_LAYER).contains(&self.layer) {
+            return invalid(format!("layer {} is out of range", self.layer));
+        }
+
+        if !(0..=u8::MAX as i16).contains(&self.spawn) {
+            return invalid(format!("spawn {} is out of range", self.spawn));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for StageInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}, {}, {})",
+            self.name, self.room, self.spawn, self.layer
+        )
+    }
+}
@@ -0,0 +1,527 @@
+use crate::gcm;
+use crate::gcm::{be_u16, be_u32};
+use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const BTI_HEADER_SIZE: usize = 0x20;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// stored deflate blocks can hold at most this many bytes each.
+const DEFLATE_BLOCK_SIZE: usize = 0xFFFF;
+
+// Format is the layout of a texture's pixels. Every format is split into
+// blocks (tiles) that are stored one after another, left to right and top to
+// bottom, with the pixels inside each block stored the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    I4,
+    I8,
+    IA4,
+    IA8,
+    RGB565,
+    RGB5A3,
+    RGBA8,
+    C4,
+    C8,
+    C14X2,
+    CMPR,
+}
+
+impl Format {
+    pub fn new(id: u8) -> io::Result<Self> {
+        match id {
+            0x0 => Ok(Self::I4),
+            0x1 => Ok(Self::I8),
+            0x2 => Ok(Self::IA4),
+            0x3 => Ok(Self::IA8),
+            0x4 => Ok(Self::RGB565),
+            0x5 => Ok(Self::RGB5A3),
+            0x6 => Ok(Self::RGBA8),
+            0x8 => Ok(Self::C4),
+            0x9 => Ok(Self::C8),
+            0xA => Ok(Self::C14X2),
+            0xE => Ok(Self::CMPR),
+            _ => Err(gcm::invalid_data("unknown texture format")),
+        }
+    }
+
+    // block_size is the width and height of each block in pixels.
+    pub fn block_size(&self) -> (usize, usize) {
+        match self {
+            Self::I4 | Self::C4 | Self::CMPR => (8, 8),
+            Self::I8 | Self::IA4 | Self::C8 => (8, 4),
+            _ => (4, 4),
+        }
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        match self {
+            Self::I4 | Self::C4 | Self::CMPR => 4,
+            Self::I8 | Self::IA4 | Self::C8 => 8,
+            Self::RGBA8 => 32,
+            _ => 16,
+        }
+    }
+
+    // data_size is the number of bytes used by an image of the given size,
+    // which is rounded up to a whole number of blocks.
+    pub fn data_size(&self, width: usize, height: usize) -> usize {
+        let (bw, bh) = self.block_size();
+        let width = width.div_ceil(bw) * bw;
+        let height = height.div_ceil(bh) * bh;
+
+        width * height * self.bits_per_pixel() / 8
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        matches!(self, Self::C4 | Self::C8 | Self::C14X2)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteFormat {
+    IA8,
+    RGB565,
+    RGB5A3,
+}
+
+impl PaletteFormat {
+    pub fn new(id: u8) -> io::Result<Self> {
+        match id {
+            0 => Ok(Self::IA8),
+            1 => Ok(Self::RGB565),
+            2 => Ok(Self::RGB5A3),
+            _ => Err(gcm::invalid_data("unknown palette format")),
+        }
+    }
+
+    fn color(&self, c: u16) -> [u8; 4] {
+        match self {
+            Self::IA8 => {
+                let i = c as u8;
+                [i, i, i, (c >> 8) as u8]
+            }
+            Self::RGB565 => rgb565(c),
+            Self::RGB5A3 => rgb5a3(c),
+        }
+    }
+}
+
+// Palette is the table of colours indexed by the C4, C8 and C14X2 formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub format: PaletteFormat,
+    pub colors: Vec<[u8; 4]>,
+}
+
+impl Palette {
+    pub fn parse(buf: &[u8], format: PaletteFormat, count: usize) -> io::Result<Self> {
+        let colors = (0..count)
+            .map(|i| Ok(format.color(be_u16(buf, i * 2)?)))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { format, colors })
+    }
+
+    fn get(&self, index: usize) -> io::Result<[u8; 4]> {
+        self.colors
+            .get(index)
+            .copied()
+            .ok_or_else(|| gcm::invalid_data("palette index is out of range"))
+    }
+}
+
+// Image is a decoded texture as 8 bit RGBA pixels, row by row.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let i = (y * self.width + x) * 4;
+        Some([
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ])
+    }
+
+    // set_pixel ignores pixels outside the image, which is where the padding
+    // of a partially filled block ends up.
+    fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        if x < self.width && y < self.height {
+            let i = (y * self.width + x) * 4;
+            self.pixels[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    // to_png encodes the image as an uncompressed PNG. PNGs can't be empty, so
+    // an image without any pixels is an error.
+    pub fn to_png(&self) -> io::Result<Vec<u8>> {
+        if self.width == 0 || self.height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a PNG must be at least 1x1",
+            ));
+        }
+
+        if self.width > u32::MAX as usize
+            || self.height > u32::MAX as usize
+            || self.width.checked_mul(self.height * 4) != Some(self.pixels.len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "image size doesn't match its pixels",
+            ));
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGBA, with the default compression, filtering
+        // and no interlacing.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut raw = Vec::with_capacity(self.height * (self.width * 4 + 1));
+        for row in self.pixels.chunks(self.width * 4) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut png, b"IEND", &[]);
+
+        Ok(png)
+    }
+
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_png()?)
+    }
+}
+
+// decode converts raw texture data to an RGBA image. Indexed formats need the
+// palette they were stored with.
+pub fn decode(
+    buf: &[u8],
+    width: usize,
+    height: usize,
+    format: Format,
+    palette: Option<&Palette>,
+) -> io::Result<Image> {
+    if buf.len() < format.data_size(width, height) {
+        return Err(gcm::invalid_data("texture data is too small"));
+    }
+
+    let palette = match (format.is_indexed(), palette) {
+        (true, None) => return Err(gcm::invalid_data("indexed texture has no palette")),
+        (_, palette) => palette,
+    };
+
+    let (bw, bh) = format.block_size();
+    let block_bytes = bw * bh * format.bits_per_pixel() / 8;
+    let blocks_wide = width.div_ceil(bw);
+
+    let mut image = Image::new(width, height);
+    for block in 0..blocks_wide * height.div_ceil(bh) {
+        let x0 = (block % blocks_wide) * bw;
+        let y0 = (block / blocks_wide) * bh;
+        let data = &buf[block * block_bytes..(block + 1) * block_bytes];
+
+        if format == Format::CMPR {
+            decode_cmpr(data, &mut image, x0, y0);
+            continue;
+        }
+
+        for i in 0..bw * bh {
+            let color = match format {
+                Format::I4 => {
+                    let i = nibble(data, i) * 0x11;
+                    [i, i, i, i]
+                }
+                Format::I8 => [data[i]; 4],
+                Format::IA4 => {
+                    let i_ = (data[i] & 0xF) * 0x11;
+                    [i_, i_, i_, (data[i] >> 4) * 0x11]
+                }
+                Format::IA8 => {
+                    let i_ = data[i * 2 + 1];
+                    [i_, i_, i_, data[i * 2]]
+                }
+                Format::RGB565 => rgb565(u16::from_be_bytes([data[i * 2], data[i * 2 + 1]])),
+                Format::RGB5A3 => rgb5a3(u16::from_be_bytes([data[i * 2], data[i * 2 + 1]])),
+                // the alpha and red of each pixel come first, then the green
+                // and blue in a second pass over the block.
+                Format::RGBA8 => [
+                    data[i * 2 + 1],
+                    data[32 + i * 2],
+                    data[32 + i * 2 + 1],
+                    data[i * 2],
+                ],
+                Format::C4 => palette.unwrap().get(nibble(data, i) as usize)?,
+                Format::C8 => palette.unwrap().get(data[i] as usize)?,
+                Format::C14X2 => {
+                    let index = u16::from_be_bytes([data[i * 2], data[i * 2 + 1]]) & 0x3FFF;
+                    palette.unwrap().get(index as usize)?
+                }
+                Format::CMPR => unreachable!(),
+            };
+
+            image.set_pixel(x0 + i % bw, y0 + i / bw, color);
+        }
+    }
+
+    Ok(image)
+}
+
+// read decodes a texture directly from game memory.
+pub fn read(
+    d: &Dolphin,
+    address: usize,
+    width: usize,
+    height: usize,
+    format: Format,
+    palette: Option<&Palette>,
+) -> io::Result<Image> {
+    let buf = d.read(format.data_size(width, height), address, None)?;
+
+    decode(&buf, width, height, format, palette)
+}
+
+// CMPR blocks are four 4x4 DXT1 sub-blocks, stored top left, top right, bottom
+// left then bottom right.
+fn decode_cmpr(data: &[u8], image: &mut Image, x0: usize, y0: usize) {
+    for (sub, block) in data.chunks_exact(8).enumerate() {
+        let c0 = u16::from_be_bytes([block[0], block[1]]);
+        let c1 = u16::from_be_bytes([block[2], block[3]]);
+        let a = rgb565(c0);
+        let b = rgb565(c1);
+
+        let mix = |wa: u16, wb: u16| {
+            let mut c = [0u8; 4];
+            for i in 0..3 {
+                c[i] = ((a[i] as u16 * wa + b[i] as u16 * wb) / (wa + wb)) as u8;
+            }
+            c[3] = 0xFF;
+            c
+        };
+        let colors = if c0 > c1 {
+            [a, b, mix(2, 1), mix(1, 2)]
+        } else {
+            [a, b, mix(1, 1), [0, 0, 0, 0]]
+        };
+
+        for y in 0..4 {
+            let row = block[4 + y];
+            for x in 0..4 {
+                let index = (row >> (6 - x * 2)) & 0x3;
+                image.set_pixel(
+                    x0 + (sub % 2) * 4 + x,
+                    y0 + (sub / 2) * 4 + y,
+                    colors[index as usize],
+                );
+            }
+        }
+    }
+}
+
+// Bti is a texture file as stored on the disc, with a header describing the
+// image and its palette.
+#[derive(Debug, Clone)]
+pub struct Bti {
+    pub format: Format,
+    pub alpha: u8,
+    pub width: usize,
+    pub height: usize,
+    pub wrap_s: u8,
+    pub wrap_t: u8,
+    pub palette: Option<Palette>,
+    pub min_filter: u8,
+    pub mag_filter: u8,
+    pub mipmaps: u8,
+    pub data: Vec<u8>,
+}
+
+impl Bti {
+    // parse reads a BTI file. Offsets in the header are relative to the header
+    // itself, so this also works on a header embedded in a model.
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        let header = buf
+            .get(..BTI_HEADER_SIZE)
+            .ok_or_else(|| gcm::invalid_data("BTI header is too small"))?;
+
+        let format = Format::new(header[0x0])?;
+        let width = be_u16(header, 0x2)? as usize;
+        let height = be_u16(header, 0x4)? as usize;
+        let palette_count = be_u16(header, 0xA)? as usize;
+        let palette_offset = be_u32(header, 0xC)? as usize;
+        let data_offset = be_u32(header, 0x1C)? as usize;
+
+        let palette = match format.is_indexed() && palette_count > 0 {
+            true => Some(Palette::parse(
+                buf.get(palette_offset..)
+                    .ok_or_else(|| gcm::invalid_data("BTI palette is out of range"))?,
+                PaletteFormat::new(header[0x9])?,
+                palette_count,
+            )?),
+            false => None,
+        };
+
+        let size = format.data_size(width, height);
+        let data = buf
+            .get(data_offset..data_offset + size)
+            .ok_or_else(|| gcm::invalid_data("BTI image data is out of range"))?
+            .to_vec();
+
+        Ok(Self {
+            format,
+            alpha: header[0x1],
+            width,
+            height,
+            wrap_s: header[0x6],
+            wrap_t: header[0x7],
+            palette,
+            min_filter: header[0x14],
+            mag_filter: header[0x15],
+            mipmaps: header[0x18],
+            data,
+        })
+    }
+
+    // image decodes the full size image, ignoring any smaller mipmaps.
+    pub fn image(&self) -> io::Result<Image> {
+        decode(
+            &self.data,
+            self.width,
+            self.height,
+            self.format,
+            self.palette.as_ref(),
+        )
+    }
+}
+
+fn nibble(data: &[u8], i: usize) -> u8 {
+    match i % 2 {
+        0 => data[i / 2] >> 4,
+        _ => data[i / 2] & 0xF,
+    }
+}
+
+fn rgb565(c: u16) -> [u8; 4] {
+    [
+        expand5((c >> 11) as u8 & 0x1F),
+        expand6((c >> 5) as u8 & 0x3F),
+        expand5(c as u8 & 0x1F),
+        0xFF,
+    ]
+}
+
+// rgb5a3 is opaque RGB555 when the top bit is set, and 3 bit alpha with
+// RGB444 otherwise.
+fn rgb5a3(c: u16) -> [u8; 4] {
+    if c & 0x8000 != 0 {
+        return [
+            expand5((c >> 10) as u8 & 0x1F),
+            expand5((c >> 5) as u8 & 0x1F),
+            expand5(c as u8 & 0x1F),
+            0xFF,
+        ];
+    }
+
+    let a = (c >> 12) as u8 & 0x7;
+    [
+        ((c >> 8) as u8 & 0xF) * 0x11,
+        ((c >> 4) as u8 & 0xF) * 0x11,
+        (c as u8 & 0xF) * 0x11,
+        (a << 5) | (a << 2) | (a >> 1),
+    ]
+}
+
+fn expand5(v: u8) -> u8 {
+    (v << 3) | (v >> 2)
+}
+
+fn expand6(v: u8) -> u8 {
+    (v << 2) | (v >> 4)
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// zlib_stored wraps data in a zlib stream without compressing it, which keeps
+// the encoder small at the cost of larger files.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(DEFLATE_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB88320,
+                _ => crc >> 1,
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for v in chunk {
+            a += *v as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+
+    (b << 16) | a
+}
@@ -0,0 +1,251 @@
+use crate::dzx;
+use crate::gcm;
+use crate::gcm::be_u32;
+use crate::player;
+use crate::rarc;
+use crate::world;
+use bit_field::BitField;
+use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::io::{Read, Seek};
+
+// 803C4D44,8 - Bitfield of the charts Link owns, see `ChartId::bit`. Triforce
+// Charts are only set once Tingle has deciphered them.
+pub const OWNED_CHARTS: usize = 0x803C4D44;
+// 803C4D4C,8 - Bitfield of the quadrants whose sunken treasure has been
+// salvaged, one bit per quadrant in `world::Quadrant::ALL` order.
+pub const SALVAGED_TREASURE: usize = 0x803C4D4C;
+
+pub const TREASURE_CHART_COUNT: u8 = 41;
+pub const TRIFORCE_CHART_COUNT: u8 = 8;
+
+// CHART_ARCHIVE holds the sea chart's data. Its chart list is a count
+// followed by an entry for every chart the sea chart can show, giving the
+// chart's type, number, sector and the spots its treasure can be at.
+pub const CHART_ARCHIVE: &str = "res/Msg/fmapres.arc";
+pub const CHART_LIST: &str = "cmapdat.bin";
+const CHART_ENTRY_SIZE: usize = 0x26;
+const CHART_SPOT_SIZE: usize = 0x8;
+// a new game picks one of these spots for each chart.
+const CHART_SPOTS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartId {
+    Treasure(u8),
+    Triforce(u8),
+}
+
+impl ChartId {
+    // bit returns the chart's position in the OWNED_CHARTS bitfield. Treasure
+    // Charts 1-41 come first, followed by Triforce Charts 1-8.
+    pub fn bit(&self) -> Option<usize> {
+        match *self {
+            Self::Treasure(n) if (1..=TREASURE_CHART_COUNT).contains(&n) => Some(n as usize - 1),
+            Self::Triforce(n) if (1..=TRIFORCE_CHART_COUNT).contains(&n) => {
+                Some((TREASURE_CHART_COUNT + n) as usize - 1)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChartId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Treasure(n) => write!(f, "Treasure Chart {}", n),
+            Self::Triforce(n) => write!(f, "Triforce Chart {}", n),
+        }
+    }
+}
+
+// TreasureChart is one entry of the catalogue: the chart itself, where it is
+// obtained if it's in a chest, and the spots in its quadrant where its treasure
+// can be salvaged. Each save file uses one of the spots.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreasureChart {
+    pub chart: ChartId,
+    pub found_at: Option<String>,
+    pub quadrant: world::Quadrant,
+    pub salvage: Vec<(f32, f32)>,
+}
+
+impl TreasureChart {
+    // parse reads an entry of the chart list, or None if it isn't a Treasure
+    // or Triforce Chart. Salvage spots are relative to the sea room of the
+    // chart's quadrant, which places them in the world.
+    fn parse(buf: &[u8], offset: usize, rooms: &[dzx::SeaRoom]) -> io::Result<Option<Self>> {
+        let entry = buf
+            .get(offset..offset + CHART_ENTRY_SIZE)
+            .ok_or_else(|| gcm::invalid_data("chart list is truncated"))?;
+
+        let chart = match entry[0x3] {
+            0 => ChartId::Treasure(entry[0x2]),
+            1 | 2 => ChartId::Triforce(entry[0x2]),
+            _ => return Ok(None),
+        };
+        if chart.bit().is_none() {
+            return Ok(None);
+        }
+
+        // sectors are counted from the middle of the map.
+        let sector = world::Sector::new(
+            (entry[0x5] as i8 as isize + 3) as usize,
+            (entry[0x4] as i8 as isize + 3) as usize,
+        )
+        .ok_or_else(|| gcm::invalid_data("chart is outside the map"))?;
+        let quadrant = sector.quadrant();
+        let room = rooms
+            .iter()
+            .find(|r| r.quadrant() == Some(quadrant))
+            .ok_or_else(|| gcm::invalid_data("chart's quadrant has no sea room"))?;
+
+        let salvage = (0..CHART_SPOTS)
+            .map(|i| {
+                let spot = 0x6 + i * CHART_SPOT_SIZE;
+                let x = i16::from_be_bytes([entry[spot + 0x2], entry[spot + 0x3]]);
+                let z = i16::from_be_bytes([entry[spot + 0x4], entry[spot + 0x5]]);
+
+                room.to_world(x as f32, z as f32)
+            })
+            .collect();
+
+        Ok(Some(Self {
+            chart,
+            found_at: None,
+            quadrant,
+            salvage,
+        }))
+    }
+
+    // distance returns how far the nearest salvage spot is from the position.
+    pub fn distance(&self, position: &player::Position) -> f32 {
+        self.salvage
+            .iter()
+            .map(|(x, z)| ((x - position.x).powi(2) + (z - position.z).powi(2)).sqrt())
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+impl fmt::Display for TreasureChart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.chart)?;
+        if let Some(found_at) = &self.found_at {
+            write!(f, " ({})", found_at)?;
+        }
+        write!(f, " -> {}", self.quadrant)?;
+
+        for (i, (x, z)) in self.salvage.iter().enumerate() {
+            let sep = if i == 0 { " at" } else { " or" };
+            write!(f, "{} x: {:.0} z: {:.0}", sep, x, z)?;
+        }
+
+        Ok(())
+    }
+}
+
+// ChartProgress is which charts Link owns and which treasures have been salvaged.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ChartProgress {
+    pub owned: HashSet<ChartId>,
+    pub salvaged: HashSet<world::Quadrant>,
+}
+
+impl ChartProgress {
+    pub fn new(owned: HashSet<ChartId>, salvaged: HashSet<world::Quadrant>) -> Self {
+        Self { owned, salvaged }
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        let owned = crate::read_u64(OWNED_CHARTS, d)?;
+        let salvaged = crate::read_u64(SALVAGED_TREASURE, d)?;
+
+        self.owned = (1..=TREASURE_CHART_COUNT)
+            .map(ChartId::Treasure)
+            .chain((1..=TRIFORCE_CHART_COUNT).map(ChartId::Triforce))
+            .filter(|chart| chart.bit().is_some_and(|bit| owned.get_bit(bit)))
+            .collect();
+        self.salvaged = world::Quadrant::ALL
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| salvaged.get_bit(*i))
+            .map(|(_, q)| *q)
+            .collect();
+
+        Ok(self.clone())
+    }
+}
+
+// TreasureCatalogue is the table of every Treasure and Triforce Chart. Every
+// quadrant holds exactly one chart's treasure, so it has one entry per square.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct TreasureCatalogue {
+    pub charts: Vec<TreasureChart>,
+}
+
+impl TreasureCatalogue {
+    pub fn new(charts: Vec<TreasureChart>) -> Self {
+        Self { charts }
+    }
+
+    // parse reads the chart list, placing each salvage spot in the world with
+    // the sea stage's rooms.
+    pub fn parse(buf: &[u8], rooms: &[dzx::SeaRoom]) -> io::Result<Self> {
+        let count = be_u32(buf, 0)? as usize;
+        let mut charts = Vec::new();
+        for i in 0..count {
+            if let Some(chart) = TreasureChart::parse(buf, 0x4 + i * CHART_ENTRY_SIZE, rooms)? {
+                charts.push(chart);
+            }
+        }
+
+        Ok(Self { charts })
+    }
+
+    pub fn from_disc<R: Read + Seek>(
+        disc: &mut gcm::Disc<R>,
+        rooms: &[dzx::SeaRoom],
+    ) -> io::Result<Self> {
+        let archive = rarc::Archive::from_disc(disc, CHART_ARCHIVE)?;
+        let (_, file) = archive
+            .files()
+            .into_iter()
+            .find(|(path, _)| path.ends_with(CHART_LIST))
+            .ok_or_else(|| gcm::invalid_data("chart archive has no chart list"))?;
+
+        Self::parse(&file.data, rooms)
+    }
+
+    pub fn get(&self, chart: ChartId) -> Option<&TreasureChart> {
+        self.charts.iter().find(|c| c.chart == chart)
+    }
+
+    pub fn in_quadrant(&self, quadrant: world::Quadrant) -> Option<&TreasureChart> {
+        self.charts.iter().find(|c| c.quadrant == quadrant)
+    }
+
+    // outstanding returns every owned chart whose treasure has not yet been
+    // salvaged, paired with the distance from the position to its nearest
+    // salvage spot, nearest first.
+    pub fn outstanding(
+        &self,
+        progress: &ChartProgress,
+        position: &player::Position,
+    ) -> Vec<(&TreasureChart, f32)> {
+        let mut outstanding: Vec<_> = self
+            .charts
+            .iter()
+            .filter(|c| {
+                progress.owned.contains(&c.chart) && !progress.salvaged.contains(&c.quadrant)
+            })
+            .map(|c| (c, c.distance(position)))
+            .collect();
+
+        outstanding.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        outstanding
+    }
+}
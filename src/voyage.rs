@@ -0,0 +1,174 @@
+use crate::korl;
+use crate::stage;
+use crate::world;
+use dolphin_memory::Dolphin;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+// the game's clock advances four in-game minutes for every degree of TIME_OF_DAY.
+const GAME_MINUTES_PER_DEGREE: f32 = 4.0;
+const GAME_MINUTES_PER_DAY: f32 = 1440.0;
+// MAX_TRAVEL_PER_SECOND is well beyond the boat's top speed, so moving further
+// than this between samples means Link warped, such as with the Ballad of Gales.
+const MAX_TRAVEL_PER_SECOND: f32 = 5000.0;
+// MIN_TRAVEL allows for samples taken very close together.
+const MIN_TRAVEL: f32 = 500.0;
+
+// Voyage is the record of a single trip on the King of Red Lions, from raising
+// the sail to Link stepping off the boat.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Voyage {
+    pub distance: f32,
+    pub duration: Duration,
+    pub game_minutes: f32,
+    pub average_speed: f32,
+    pub max_speed: f32,
+    pub unfavourable_wind: Duration,
+    pub quadrants: Vec<world::Quadrant>,
+}
+
+impl fmt::Display for Voyage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} units in {:.2}s ({:.0} in-game minutes), average speed: {:.2} max speed: {:.2}",
+            self.distance,
+            self.duration.as_secs_f32(),
+            self.game_minutes,
+            self.average_speed,
+            self.max_speed
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Leg {
+    voyage: Voyage,
+    started: Instant,
+    updated: Instant,
+    start_time: world::WorldTime,
+    last_position: (f32, f32),
+    last_stage: String,
+    // speed_time is the sum of each sample's speed multiplied by the seconds
+    // it covers, so that the average isn't skewed by irregular polling.
+    speed_time: f32,
+    seconds: f32,
+}
+
+// VoyageTracker watches the King of Red Lions and emits a `Voyage` each time a
+// trip ends. It should be updated as often as the caller is able to poll.
+#[derive(Debug, Clone)]
+pub struct VoyageTracker {
+    map: world::Map,
+    leg: Option<Leg>,
+}
+
+impl Default for VoyageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VoyageTracker {
+    pub fn new() -> Self {
+        Self {
+            map: world::Map::new(),
+            leg: None,
+        }
+    }
+
+    pub fn is_sailing(&self) -> bool {
+        self.leg.is_some()
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Option<Voyage>> {
+        let stage_id = stage::StageId::default().read(d)?;
+        let stage: String = stage::StageName::default().read(d)?.into();
+        let korl = korl::KingOfRedLions::default().read(d)?;
+        let wind = korl::Wind::default().read(d)?;
+        let time = world::WorldTime::default().read(d)?;
+        let quadrant = self.map.current_quadrant(&stage_id, &korl.position);
+
+        Ok(self.update(&korl, &wind, &time, &stage, quadrant, Instant::now()))
+    }
+
+    pub fn update(
+        &mut self,
+        korl: &korl::KingOfRedLions,
+        wind: &korl::Wind,
+        time: &world::WorldTime,
+        stage: &str,
+        quadrant: world::Quadrant,
+        now: Instant,
+    ) -> Option<Voyage> {
+        let position = (korl.position.x, korl.position.z);
+
+        let leg = match self.leg.as_mut() {
+            Some(leg) => leg,
+            None => {
+                if korl.link_aboard && korl.is_sail_up() {
+                    self.leg = Some(Leg {
+                        voyage: Voyage {
+                            quadrants: vec![quadrant],
+                            ..Voyage::default()
+                        },
+                        started: now,
+                        updated: now,
+                        start_time: *time,
+                        last_position: position,
+                        last_stage: stage.to_owned(),
+                        speed_time: 0.0,
+                        seconds: 0.0,
+                    });
+                }
+
+                return None;
+            }
+        };
+
+        if !korl.link_aboard {
+            return self.leg.take().map(|leg| leg.voyage);
+        }
+
+        let elapsed = now.duration_since(leg.updated);
+        let speed: f32 = korl.speed.into();
+        let voyage = &mut leg.voyage;
+
+        let travelled = ((position.0 - leg.last_position.0).powi(2)
+            + (position.1 - leg.last_position.1).powi(2))
+        .sqrt();
+        let warped = stage != leg.last_stage
+            || travelled > MIN_TRAVEL.max(MAX_TRAVEL_PER_SECOND * elapsed.as_secs_f32());
+
+        voyage.duration = now.duration_since(leg.started);
+        voyage.game_minutes = (time.day as f32 - leg.start_time.day as f32) * GAME_MINUTES_PER_DAY
+            + (time.time - leg.start_time.time) * GAME_MINUTES_PER_DEGREE;
+
+        // a warp isn't sailed, so it counts towards neither distance nor speed.
+        if !warped {
+            voyage.distance += travelled;
+            leg.speed_time += speed.abs() * elapsed.as_secs_f32();
+            leg.seconds += elapsed.as_secs_f32();
+            if leg.seconds > 0.0 {
+                voyage.average_speed = leg.speed_time / leg.seconds;
+            }
+            voyage.max_speed = voyage.max_speed.max(speed.abs());
+        }
+
+        if korl.is_sail_up() && !wind.is_favourable(korl.bearing()) {
+            voyage.unfavourable_wind += elapsed;
+        }
+
+        if voyage.quadrants.last() != Some(&quadrant) {
+            voyage.quadrants.push(quadrant);
+        }
+
+        leg.last_position = position;
+        leg.last_stage = stage.to_owned();
+        leg.updated = now;
+
+        None
+    }
+}
@@ -1,14 +1,40 @@
 use crate::player;
 use crate::stage;
+use bit_field::BitField;
+use dolphin_memory::Dolphin;
+use std::collections::{HashMap, HashSet};
+use std::error;
 use std::fmt;
+use std::io;
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
 const MAP_CELLS: usize = 7;
-const MAP_RANGE: (f32, f32) = (-350000.0, 350000.0);
+// the Great Sea's north west corner, and the size of each of its squares.
+const MAP_ORIGIN: (f32, f32) = (-350000.0, -350000.0);
 const QUADRANT_SIZE: f32 = 100000.0;
+// sector notation uses a single letter and digit.
+const MAX_SECTOR_COLS: usize = 26;
+const MAX_SECTOR_ROWS: usize = 9;
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+// 803C4C9C,4 - Time of day. Float from 0 to 360, where each hour is 15 degrees.
+pub const TIME_OF_DAY: usize = 0x803C4C9C;
+// 803C4CA0,2 - Number of days that have passed since the start of the game.
+pub const DAY_COUNTER: usize = 0x803C4CA0;
+
+// 803C4CC4,8 - Bitfield of the sea chart squares Link has sailed into, one bit
+// per quadrant in grid order starting from the least significant bit.
+pub const VISITED_SECTORS: usize = 0x803C4CC4;
+// 803C4CCC,8 - Bitfield of the squares whose island has been charted by a
+// fishman, laid out the same as VISITED_SECTORS.
+pub const CHARTED_SECTORS: usize = 0x803C4CCC;
+
+const DEGREES_PER_HOUR: f32 = 15.0;
+// the night begins at 18:00 and ends at 06:00.
+const NIGHT_HOURS: (u8, u8) = (18, 6);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Quadrant {
     // row 1
     ForsakenFortress,
@@ -73,10 +99,73 @@ pub enum Quadrant {
     BoatingCourse,
     FiveStarIsles,
 
+    // the open ocean beyond the edge of the sea chart
+    Outside,
     Unknown,
 }
 
 impl Quadrant {
+    // ALL lists every quadrant on the sea chart in grid order, row by row from
+    // the north west corner.
+    pub const ALL: [Quadrant; 49] = [
+        // row 1
+        Self::ForsakenFortress,
+        Self::StarIsland,
+        Self::NorthernFairyIsland,
+        Self::GaleIsle,
+        Self::CresentMoonIsland,
+        Self::SevenStarIsles,
+        Self::OverlookIsland,
+        // row 2
+        Self::FourEyeReef,
+        Self::MotherAndChildIsles,
+        Self::SpectacleIsland,
+        Self::WindfallIsland,
+        Self::PawprintIsle,
+        Self::DragonRoostIsland,
+        Self::FlightControlPlatform,
+        // row 3
+        Self::WesternFairyIsland,
+        Self::RockSpireIsle,
+        Self::TingleIsland,
+        Self::NorthernTriangleIsle,
+        Self::EasternFairyIsland,
+        Self::FireMountain,
+        Self::StarBeltArchipelago,
+        // row 4
+        Self::ThreeEyeRoof,
+        Self::GreatfishIsle,
+        Self::CyclopsReef,
+        Self::SixEyeReef,
+        Self::TowerOfTheGods,
+        Self::EasternTriangleIsland,
+        Self::ThornedFairyIsland,
+        // row 5
+        Self::NeedleRockIsle,
+        Self::IsletOfSteel,
+        Self::StoneWatcherIsland,
+        Self::SouthernTriangleIsland,
+        Self::PrivateOasis,
+        Self::BombIsland,
+        Self::BirdsPeakRock,
+        // row 6
+        Self::DiamondSteppeIsland,
+        Self::FiveEyeReef,
+        Self::SharkIsland,
+        Self::SouthernFairyIsland,
+        Self::IceRingIsle,
+        Self::ForestHaven,
+        Self::CliffPlateauIsles,
+        // row 7
+        Self::HorseshoeIsland,
+        Self::OutsetIsland,
+        Self::HeadstoneIsland,
+        Self::TwoEyeReef,
+        Self::AngularIsles,
+        Self::BoatingCourse,
+        Self::FiveStarIsles,
+    ];
+
     pub fn new(position: (usize, usize)) -> Self {
         match position {
             // row 1
@@ -145,6 +234,20 @@ impl Quadrant {
             _ => Self::Unknown,
         }
     }
+
+    // grid_position returns the (row, col) of this quadrant on the Great Sea,
+    // the inverse of `Quadrant::new`. Use `Map::sector` for other grids.
+    pub fn grid_position(&self) -> Option<(usize, usize)> {
+        let index = Self::ALL.iter().position(|q| q == self)?;
+
+        Some((index / MAP_CELLS, index % MAP_CELLS))
+    }
+
+    pub fn sector(&self) -> Option<Sector> {
+        let (row, col) = self.grid_position()?;
+
+        Some(Sector { row, col })
+    }
 }
 
 impl fmt::Display for Quadrant {
@@ -213,26 +316,255 @@ impl fmt::Display for Quadrant {
             Self::BoatingCourse => write!(f, "Boating Course"),
             Self::FiveStarIsles => write!(f, "Five-Star Isles"),
 
+            Self::Outside => write!(f, "Outside the map"),
             Self::Unknown => write!(f, "Unknown"),
         }
     }
 }
 
-#[derive(Default, Debug, Clone)]
+// normalise_name reduces a quadrant name to a form that ignores case,
+// punctuation and whether the name ends in "island", "isle" or "isles".
+fn normalise_name(name: &str) -> String {
+    let name: String = name
+        .to_lowercase()
+        .replace('&', "and")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+
+    for suffix in ["islands", "island", "isles", "isle"] {
+        if let Some(stripped) = name.strip_suffix(suffix) {
+            if !stripped.is_empty() {
+                return stripped.to_owned();
+            }
+        }
+    }
+
+    name
+}
+
+// QUADRANT_ALIASES are the names and abbreviations players commonly use for
+// quadrants that don't match either the variant or the display name.
+const QUADRANT_ALIASES: [(&str, Quadrant); 12] = [
+    ("ff", Quadrant::ForsakenFortress),
+    ("totg", Quadrant::TowerOfTheGods),
+    ("tower", Quadrant::TowerOfTheGods),
+    ("dri", Quadrant::DragonRoostIsland),
+    ("fh", Quadrant::ForestHaven),
+    ("fm", Quadrant::FireMountain),
+    ("ir", Quadrant::IceRingIsle),
+    ("nti", Quadrant::NorthernTriangleIsle),
+    ("eti", Quadrant::EasternTriangleIsland),
+    ("sti", Quadrant::SouthernTriangleIsland),
+    ("motherchild", Quadrant::MotherAndChildIsles),
+    ("birdspeak", Quadrant::BirdsPeakRock),
+];
+
+impl FromStr for Quadrant {
+    type Err = ParseError;
+
+    // from_str accepts display names, variant names, common aliases and chart
+    // sector notation such as "D2".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = normalise_name(s);
+        match name.as_str() {
+            "unknown" => return Ok(Self::Unknown),
+            "outside" | "outsidethemap" => return Ok(Self::Outside),
+            _ => {}
+        }
+
+        let quadrant = Self::ALL.iter().copied().find(|q| {
+            normalise_name(&q.to_string()) == name || normalise_name(&format!("{:?}", q)) == name
+        });
+        if let Some(quadrant) = quadrant {
+            return Ok(quadrant);
+        }
+
+        if let Some((_, quadrant)) = QUADRANT_ALIASES.iter().find(|(alias, _)| *alias == name) {
+            return Ok(*quadrant);
+        }
+
+        // sector notation can name squares of larger grids, so only accept
+        // sectors on the Great Sea.
+        s.parse::<Sector>()
+            .ok()
+            .filter(|sector| sector.row < MAP_CELLS && sector.col < MAP_CELLS)
+            .map(|sector| sector.quadrant())
+            .ok_or_else(|| ParseError::new(s))
+    }
+}
+
+// display_name serializes a quadrant using its display name rather than its
+// variant name, and accepts anything `Quadrant::from_str` does when deserializing.
+// Use it with `#[serde(with = "windwaker::world::display_name")]`.
+pub mod display_name {
+    use super::Quadrant;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(quadrant: &Quadrant, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(quadrant)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Quadrant, D::Error> {
+        let raw = String::deserialize(d)?;
+
+        raw.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl ParseError {
+    pub fn new(raw: &str) -> Self {
+        Self(raw.to_owned())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unable to parse '{}'", self.0)
+    }
+}
+
+impl error::Error for ParseError {}
+
+// Sector is a square of the sea chart in the notation players use, where the
+// column is a letter from A to G (west to east) and the row is a number from
+// 1 to 7 (north to south). Forsaken Fortress is A1 and Outset Island is B7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sector {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Sector {
+    // new returns the sector if it can be written in sector notation. Whether
+    // it exists depends on the map, see `Map::quadrant_in`.
+    pub fn new(row: usize, col: usize) -> Option<Self> {
+        if row >= MAX_SECTOR_ROWS || col >= MAX_SECTOR_COLS {
+            return None;
+        }
+
+        Some(Self { row, col })
+    }
+
+    // quadrant returns the quadrant in this sector of the Great Sea.
+    pub fn quadrant(&self) -> Quadrant {
+        Quadrant::new((self.row, self.col))
+    }
+}
+
+impl FromStr for Sector {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.trim().chars();
+        let (letter, number) = match (chars.next(), chars.next(), chars.next()) {
+            (Some(letter), Some(number), None) => (letter.to_ascii_uppercase(), number),
+            _ => return Err(ParseError::new(s)),
+        };
+
+        let col = (letter as usize).wrapping_sub('A' as usize);
+        let row = number
+            .to_digit(10)
+            .and_then(|n| (n as usize).checked_sub(1))
+            .ok_or_else(|| ParseError::new(s))?;
+
+        Self::new(row, col).ok_or_else(|| ParseError::new(s))
+    }
+}
+
+impl fmt::Display for Sector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'A' + self.col as u8) as char, self.row + 1)
+    }
+}
+
+// Chart converts between world XZ coordinates and pixel coordinates on a sea
+// chart image covering a whole map, with (0, 0) in the top left (north west).
+#[derive(Debug, Clone, Copy)]
+pub struct Chart {
+    pub width: u32,
+    pub height: u32,
+    pub origin: (f32, f32),
+    pub size: (f32, f32),
+}
+
+impl Chart {
+    // new returns a chart of the Great Sea.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::for_map(&Map::new(), width, height)
+    }
+
+    pub fn for_map(map: &Map, width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            origin: map.origin,
+            size: map.extent(),
+        }
+    }
+
+    pub fn to_pixel(&self, x: f32, z: f32) -> (f64, f64) {
+        (
+            (x as f64 - self.origin.0 as f64) * self.width as f64 / self.size.0 as f64,
+            (z as f64 - self.origin.1 as f64) * self.height as f64 / self.size.1 as f64,
+        )
+    }
+
+    pub fn to_world(&self, px: f64, py: f64) -> (f32, f32) {
+        (
+            (px * self.size.0 as f64 / self.width as f64 + self.origin.0 as f64) as f32,
+            (py * self.size.1 as f64 / self.height as f64 + self.origin.1 as f64) as f32,
+        )
+    }
+}
+
+// Map is a grid of quadrants laid over the sea. Each row runs west to east and
+// rows run north to south, starting from the north west corner at `origin`.
+// The default map is the Great Sea, but any grid can be described, such as the
+// rearranged seas of randomiser variants. `islands` holds where each island
+// really is, such as the positions in `data::GameData`.
+#[derive(Debug, Clone)]
 pub struct Map {
     pub grid: Vec<Vec<Quadrant>>,
+    pub origin: (f32, f32),
+    pub cell_size: f32,
+    pub islands: HashMap<Quadrant, (f32, f32)>,
+}
+
+impl Default for Map {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Map {
     pub fn new() -> Self {
-        let mut grid = vec![vec![Quadrant::Unknown; MAP_CELLS]; MAP_CELLS];
-        for row in 0..7usize {
-            for col in 0..7usize {
-                grid[row][col] = Quadrant::new((row, col));
-            }
+        let grid = Quadrant::ALL
+            .chunks(MAP_CELLS)
+            .map(|row| row.to_vec())
+            .collect();
+
+        Self::with_grid(grid, MAP_ORIGIN, QUADRANT_SIZE)
+    }
+
+    pub fn with_grid(grid: Vec<Vec<Quadrant>>, origin: (f32, f32), cell_size: f32) -> Self {
+        Self {
+            grid,
+            origin,
+            cell_size,
+            islands: HashMap::new(),
         }
+    }
 
-        Self { grid }
+    // with_islands returns the Great Sea with its islands at the given positions.
+    pub fn with_islands(islands: HashMap<Quadrant, (f32, f32)>) -> Self {
+        Self {
+            islands,
+            ..Self::new()
+        }
     }
 
     pub fn current_quadrant(
@@ -246,53 +578,621 @@ impl Map {
             _ => return Quadrant::Unknown,
         };
 
-        if position.x < MAP_RANGE.0
-            || position.x > MAP_RANGE.1
-            || position.z < MAP_RANGE.0
-            || position.z > MAP_RANGE.1
-        {
+        self.quadrant_at(position.x, position.z)
+    }
+
+    // quadrant_at returns the quadrant containing the XZ coordinates. Cells
+    // include their north and west edges, so a position exactly on the line
+    // between two cells belongs to the one to its south or east, except on the
+    // outer edge of the map which belongs to the cell inside it.
+    pub fn quadrant_at(&self, x: f32, z: f32) -> Quadrant {
+        if !x.is_finite() || !z.is_finite() {
             return Quadrant::Unknown;
         }
 
-        for rect in self.quadrant_rects() {
-            if position.x >= rect.from.0
-                && position.x <= rect.to.0
-                && position.z >= rect.from.1
-                && position.z <= rect.to.1
-            {
-                return rect.quadrant;
-            }
+        let row = match Self::cell_index(z - self.origin.1, self.cell_size, self.grid.len()) {
+            Some(row) => row,
+            None => return Quadrant::Outside,
+        };
+        let line = &self.grid[row];
+
+        match Self::cell_index(x - self.origin.0, self.cell_size, line.len()) {
+            Some(col) => line[col],
+            None => Quadrant::Outside,
         }
+    }
+
+    // extent returns the width (X) and height (Z) the map covers.
+    pub fn extent(&self) -> (f32, f32) {
+        let cols = self.grid.iter().map(|line| line.len()).max().unwrap_or(0);
 
-        return Quadrant::Unknown;
+        (
+            cols as f32 * self.cell_size,
+            self.grid.len() as f32 * self.cell_size,
+        )
     }
 
-    fn quadrant_rects(&self) -> Vec<Rect> {
-        let mut rects = Vec::new();
+    // sector returns where the quadrant sits on this map.
+    pub fn sector(&self, quadrant: Quadrant) -> Option<Sector> {
+        self.grid.iter().enumerate().find_map(|(row, line)| {
+            let col = line.iter().position(|q| *q == quadrant)?;
+
+            Sector::new(row, col)
+        })
+    }
+
+    // quadrant_in returns the quadrant in the sector of this map, or Outside
+    // if the map doesn't reach that far.
+    pub fn quadrant_in(&self, sector: Sector) -> Quadrant {
+        self.grid
+            .get(sector.row)
+            .and_then(|line| line.get(sector.col))
+            .copied()
+            .unwrap_or(Quadrant::Outside)
+    }
+
+    // island_position returns the XZ coordinates of the quadrant's island, or
+    // the centre of its square if the map doesn't know where the island is.
+    pub fn island_position(&self, quadrant: Quadrant) -> Option<(f32, f32)> {
+        self.islands
+            .get(&quadrant)
+            .copied()
+            .or_else(|| self.cell_centre(quadrant))
+    }
+
+    // cell_centre returns the XZ coordinates of the centre of the cell the
+    // quadrant occupies on this map.
+    pub fn cell_centre(&self, quadrant: Quadrant) -> Option<(f32, f32)> {
+        self.grid.iter().enumerate().find_map(|(row, line)| {
+            let col = line.iter().position(|q| *q == quadrant)?;
+
+            Some((
+                self.origin.0 + (col as f32 + 0.5) * self.cell_size,
+                self.origin.1 + (row as f32 + 0.5) * self.cell_size,
+            ))
+        })
+    }
+
+    // nearest_island returns the quadrant whose island is closest to the position.
+    pub fn nearest_island(&self, position: &player::Position) -> Quadrant {
+        self.grid
+            .iter()
+            .flatten()
+            .filter_map(|quadrant| Some((*quadrant, self.distance_to(position, *quadrant)?)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(quadrant, _)| quadrant)
+            .unwrap_or(Quadrant::Unknown)
+    }
+
+    // distance_to returns the straight-line XZ distance from the position to
+    // the quadrant's island.
+    pub fn distance_to(&self, position: &player::Position, quadrant: Quadrant) -> Option<f32> {
+        let (x, z) = self.island_position(quadrant)?;
+
+        Some(((x - position.x).powi(2) + (z - position.z).powi(2)).sqrt())
+    }
+
+    // bearing_to returns the bearing from the position to the quadrant's
+    // island, in degrees clockwise from north.
+    pub fn bearing_to(&self, position: &player::Position, quadrant: Quadrant) -> Option<f32> {
+        let island = self.island_position(quadrant)?;
+
+        Some(bearing((position.x, position.z), island))
+    }
+
+    pub fn landmark(&self, position: &player::Position, quadrant: Quadrant) -> Option<Landmark> {
+        Some(Landmark {
+            quadrant,
+            distance: self.distance_to(position, quadrant)?,
+            bearing: self.bearing_to(position, quadrant)?,
+        })
+    }
+
+    // neighbours returns the up to eight quadrants surrounding the given one.
+    pub fn neighbours(&self, quadrant: Quadrant) -> Vec<Quadrant> {
+        let mut neighbours = Vec::new();
 
         for (row, line) in self.grid.iter().enumerate() {
-            for (col, quadrant) in line.iter().enumerate() {
-                let from = (
-                    MAP_RANGE.0 + (col as f32 * QUADRANT_SIZE),
-                    MAP_RANGE.0 + (row as f32 * QUADRANT_SIZE),
-                );
-                let to = (from.0 + QUADRANT_SIZE, from.1 + QUADRANT_SIZE);
-
-                rects.push(Rect {
-                    quadrant: *quadrant,
-                    from,
-                    to,
-                });
+            for (col, q) in line.iter().enumerate() {
+                if *q != quadrant {
+                    continue;
+                }
+
+                for r in row.saturating_sub(1)..=(row + 1).min(self.grid.len() - 1) {
+                    for c in col.saturating_sub(1)..=(col + 1).min(self.grid[r].len() - 1) {
+                        if (r, c) != (row, col) {
+                            neighbours.push(self.grid[r][c]);
+                        }
+                    }
+                }
             }
         }
 
-        rects
+        neighbours
+    }
+
+    // cell_index returns which of `cells` cells an offset from the map origin
+    // falls into, or None if it lies outside of the map.
+    fn cell_index(offset: f32, cell_size: f32, cells: usize) -> Option<usize> {
+        let extent = cells as f32 * cell_size;
+        if cells == 0 || offset < 0.0 || offset > extent {
+            return None;
+        }
+
+        Some(((offset / cell_size) as usize).min(cells - 1))
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Rect {
-    quadrant: Quadrant,
-    from: (f32, f32),
-    to: (f32, f32),
+// bearing returns the bearing from one XZ position to another, in degrees
+// clockwise from north.
+pub fn bearing(from: (f32, f32), to: (f32, f32)) -> f32 {
+    // north is -Z, so bearings are measured from the negated Z axis.
+    (to.0 - from.0)
+        .atan2(from.1 - to.1)
+        .to_degrees()
+        .rem_euclid(360.0)
+}
+
+// Landmark describes where a quadrant's island lies relative to a position.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Landmark {
+    pub quadrant: Quadrant,
+    pub distance: f32,
+    pub bearing: f32,
+}
+
+impl Landmark {
+    pub fn direction(&self) -> Compass {
+        Compass::new(self.bearing)
+    }
+}
+
+impl fmt::Display for Landmark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = format!("{:.0}", self.distance);
+        let mut distance = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                distance.push(',');
+            }
+            distance.push(c);
+        }
+
+        write!(
+            f,
+            "{}: {} units {}",
+            self.quadrant,
+            distance,
+            self.direction()
+        )
+    }
+}
+
+// bearing_from_angle converts an in-game angle, where 0x10000 is a full turn,
+// 0 faces south (+Z) and 0x4000 faces east (+X), into degrees clockwise from north.
+pub fn bearing_from_angle(angle: u16) -> f32 {
+    let degrees = angle as f32 * 360.0 / 65536.0;
+
+    (180.0 - degrees).rem_euclid(360.0)
+}
+
+// angle_from_bearing is the inverse of bearing_from_angle.
+pub fn angle_from_bearing(bearing: f32) -> u16 {
+    let degrees = (180.0 - bearing).rem_euclid(360.0);
+
+    (degrees * 65536.0 / 360.0).round() as u32 as u16
+}
+
+// Compass is one of the eight compass points shown on the sea chart. North is
+// towards Forsaken Fortress (-Z) and east is towards Overlook Island (+X).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Compass {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Compass {
+    pub const ALL: [Compass; 8] = [
+        Self::North,
+        Self::NorthEast,
+        Self::East,
+        Self::SouthEast,
+        Self::South,
+        Self::SouthWest,
+        Self::West,
+        Self::NorthWest,
+    ];
+
+    // new returns the compass point nearest to a bearing in degrees, measured
+    // clockwise from north.
+    pub fn new(bearing: f32) -> Self {
+        let index = (bearing.rem_euclid(360.0) / 45.0).round() as usize % 8;
+
+        Self::ALL[index]
+    }
+
+    // bearing returns the compass point in degrees clockwise from north.
+    pub fn bearing(&self) -> f32 {
+        let index = Self::ALL.iter().position(|c| c == self).unwrap_or(0);
+
+        index as f32 * 45.0
+    }
+}
+
+impl fmt::Display for Compass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::North => write!(f, "N"),
+            Self::NorthEast => write!(f, "NE"),
+            Self::East => write!(f, "E"),
+            Self::SouthEast => write!(f, "SE"),
+            Self::South => write!(f, "S"),
+            Self::SouthWest => write!(f, "SW"),
+            Self::West => write!(f, "W"),
+            Self::NorthWest => write!(f, "NW"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DayOfWeek {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl DayOfWeek {
+    pub fn new(day: u16) -> Self {
+        match day % 7 {
+            0 => Self::Sunday,
+            1 => Self::Monday,
+            2 => Self::Tuesday,
+            3 => Self::Wednesday,
+            4 => Self::Thursday,
+            5 => Self::Friday,
+            _ => Self::Saturday,
+        }
+    }
+}
+
+impl fmt::Display for DayOfWeek {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// MoonPhase follows the day counter, cycling through all eight phases once
+// every eight days starting from a full moon on the first day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MoonPhase {
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+}
+
+impl MoonPhase {
+    pub fn new(day: u16) -> Self {
+        match day % 8 {
+            0 => Self::Full,
+            1 => Self::WaningGibbous,
+            2 => Self::LastQuarter,
+            3 => Self::WaningCrescent,
+            4 => Self::New,
+            5 => Self::WaxingCrescent,
+            6 => Self::FirstQuarter,
+            _ => Self::WaxingGibbous,
+        }
+    }
+}
+
+impl fmt::Display for MoonPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Full => write!(f, "Full Moon"),
+            Self::WaningGibbous => write!(f, "Waning Gibbous"),
+            Self::LastQuarter => write!(f, "Last Quarter"),
+            Self::WaningCrescent => write!(f, "Waning Crescent"),
+            Self::New => write!(f, "New Moon"),
+            Self::WaxingCrescent => write!(f, "Waxing Crescent"),
+            Self::FirstQuarter => write!(f, "First Quarter"),
+            Self::WaxingGibbous => write!(f, "Waxing Gibbous"),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct WorldTime {
+    pub time: f32,
+    pub day: u16,
+}
+
+impl WorldTime {
+    pub fn new(time: f32, day: u16) -> Self {
+        Self { time, day }
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.time = d.read_f32(TIME_OF_DAY, None)?;
+        self.day = d.read_u16(DAY_COUNTER, None)?;
+
+        Ok(*self)
+    }
+
+    // write_time sets the clock to the given hour (0-23) and minute (0-59).
+    pub fn write_time(&mut self, hour: u8, minute: u8, d: &Dolphin) -> io::Result<Self> {
+        if hour > 23 || minute > 59 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid time of day: {:02}:{:02}", hour, minute),
+            ));
+        }
+
+        let time = degrees(hour, minute);
+        d.write_f32(time, TIME_OF_DAY, None)?;
+        self.time = time;
+
+        Ok(*self)
+    }
+
+    pub fn write_day(&mut self, day: u16, d: &Dolphin) -> io::Result<Self> {
+        d.write(&day.to_be_bytes(), DAY_COUNTER, None)?;
+        self.day = day;
+
+        Ok(*self)
+    }
+
+    pub fn hours(&self) -> u8 {
+        (self.total_minutes() / 60) as u8
+    }
+
+    pub fn minutes(&self) -> u8 {
+        (self.total_minutes() % 60) as u8
+    }
+
+    // total_minutes is the time of day to the nearest minute. Rounding rather
+    // than truncating keeps times set by `write_time` from reading back a
+    // minute early.
+    fn total_minutes(&self) -> u32 {
+        (self.time / DEGREES_PER_HOUR * 60.0).round() as u32 % (24 * 60)
+    }
+
+    pub fn day_of_week(&self) -> DayOfWeek {
+        DayOfWeek::new(self.day)
+    }
+
+    pub fn moon_phase(&self) -> MoonPhase {
+        MoonPhase::new(self.day)
+    }
+
+    pub fn is_night(&self) -> bool {
+        let hours = self.hours();
+
+        hours >= NIGHT_HOURS.0 || hours < NIGHT_HOURS.1
+    }
+
+    // is_ghost_ship_night returns whether the Ghost Ship is out tonight. It sails
+    // every night except during a new moon, moving between the squares marked
+    // on the Ghost Ship Chart as the moon changes.
+    pub fn is_ghost_ship_night(&self) -> bool {
+        self.is_night() && self.moon_phase() != MoonPhase::New
+    }
+}
+
+// degrees converts a time of day to the clock's angle.
+fn degrees(hour: u8, minute: u8) -> f32 {
+    (hour as f32 + minute as f32 / 60.0) * DEGREES_PER_HOUR
+}
+
+impl fmt::Display for WorldTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Day {} ({}) {:02}:{:02}, {}",
+            self.day,
+            self.day_of_week(),
+            self.hours(),
+            self.minutes(),
+            self.moon_phase()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Discovery {
+    Visited(Quadrant),
+    Charted(Quadrant),
+}
+
+impl fmt::Display for Discovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Visited(quadrant) => write!(f, "Visited {}", quadrant),
+            Self::Charted(quadrant) => write!(f, "Charted {}", quadrant),
+        }
+    }
+}
+
+// SeaChart is the progress shown on the in-game sea chart: the squares that
+// have been sailed into and the squares that a fishman has charted.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SeaChart {
+    pub visited: HashSet<Quadrant>,
+    pub charted: HashSet<Quadrant>,
+    // whether the chart holds a known starting point to compare changes with.
+    #[serde(skip)]
+    initialised: bool,
+}
+
+impl SeaChart {
+    pub fn new(visited: HashSet<Quadrant>, charted: HashSet<Quadrant>) -> Self {
+        Self {
+            visited,
+            charted,
+            initialised: true,
+        }
+    }
+
+    pub fn read(&mut self, d: &Dolphin) -> io::Result<Self> {
+        self.visited = Self::read_sectors(VISITED_SECTORS, d)?;
+        self.charted = Self::read_sectors(CHARTED_SECTORS, d)?;
+        self.initialised = true;
+
+        Ok(self.clone())
+    }
+
+    // read_changes reads the chart and returns every square that has been
+    // visited or charted since the last read, in grid order. The first read
+    // of a chart that hasn't been read or given a starting point only records
+    // the current progress, so the existing save isn't reported as new.
+    pub fn read_changes(&mut self, d: &Dolphin) -> io::Result<Vec<Discovery>> {
+        if !self.initialised {
+            self.read(d)?;
+            return Ok(Vec::new());
+        }
+
+        let previous = self.clone();
+        self.read(d)?;
+
+        let visited = Quadrant::ALL
+            .iter()
+            .filter(|q| self.visited.contains(q) && !previous.visited.contains(q))
+            .map(|q| Discovery::Visited(*q));
+        let charted = Quadrant::ALL
+            .iter()
+            .filter(|q| self.charted.contains(q) && !previous.charted.contains(q))
+            .map(|q| Discovery::Charted(*q));
+
+        Ok(visited.chain(charted).collect())
+    }
+
+    pub fn write_visited(&mut self, visited: HashSet<Quadrant>, d: &Dolphin) -> io::Result<Self> {
+        Self::write_sectors(&visited, VISITED_SECTORS, d)?;
+        self.visited = visited;
+
+        Ok(self.clone())
+    }
+
+    pub fn write_charted(&mut self, charted: HashSet<Quadrant>, d: &Dolphin) -> io::Result<Self> {
+        Self::write_sectors(&charted, CHARTED_SECTORS, d)?;
+        self.charted = charted;
+
+        Ok(self.clone())
+    }
+
+    fn read_sectors(address: usize, d: &Dolphin) -> io::Result<HashSet<Quadrant>> {
+        let bits = crate::read_u64(address, d)?;
+
+        Ok(Quadrant::ALL
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| bits.get_bit(*i))
+            .map(|(_, q)| *q)
+            .collect())
+    }
+
+    fn write_sectors(sectors: &HashSet<Quadrant>, address: usize, d: &Dolphin) -> io::Result<()> {
+        let mut bits = 0u64;
+        for (i, quadrant) in Quadrant::ALL.iter().enumerate() {
+            bits.set_bit(i, sectors.contains(quadrant));
+        }
+
+        d.write(&bits.to_be_bytes(), address, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_display_names() {
+        for quadrant in Quadrant::ALL {
+            assert_eq!(quadrant.to_string().parse(), Ok(quadrant));
+            assert_eq!(format!("{:?}", quadrant).parse(), Ok(quadrant));
+        }
+    }
+
+    #[test]
+    fn parse_aliases() {
+        for (alias, quadrant) in QUADRANT_ALIASES {
+            assert_eq!(alias.parse(), Ok(quadrant));
+            assert_eq!(alias.to_uppercase().parse(), Ok(quadrant));
+        }
+    }
+
+    #[test]
+    fn parse_sectors() {
+        assert_eq!("A1".parse(), Ok(Quadrant::ForsakenFortress));
+        assert_eq!("b7".parse(), Ok(Quadrant::OutsetIsland));
+        for (i, quadrant) in Quadrant::ALL.iter().enumerate() {
+            let sector = format!(
+                "{}{}",
+                (b'A' + (i % MAP_CELLS) as u8) as char,
+                i / MAP_CELLS + 1
+            );
+
+            assert_eq!(sector.parse(), Ok(*quadrant));
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_sectors() {
+        for raw in ["H1", "Z9", "A8", "G9", "A0", "A", "A10", "11", ""] {
+            assert_eq!(
+                raw.parse::<Quadrant>(),
+                Err(ParseError::new(raw)),
+                "{}",
+                raw
+            );
+        }
+    }
+
+    #[test]
+    fn island_positions() {
+        let islands = HashMap::from([(Quadrant::OutsetIsland, (-200000.0, 320000.0))]);
+        let map = Map::with_islands(islands);
+
+        assert_eq!(
+            map.island_position(Quadrant::OutsetIsland),
+            Some((-200000.0, 320000.0))
+        );
+        assert_eq!(
+            map.island_position(Quadrant::ForsakenFortress),
+            Some((-300000.0, -300000.0))
+        );
+        assert_eq!(map.island_position(Quadrant::Unknown), None);
+    }
+
+    #[test]
+    fn time_round_trip() {
+        for hour in 0..24 {
+            for minute in 0..60 {
+                let time = WorldTime::new(degrees(hour, minute), 0);
+
+                assert_eq!((time.hours(), time.minutes()), (hour, minute));
+            }
+        }
+    }
+
+    #[test]
+    fn time_wraps_at_midnight() {
+        let time = WorldTime::new(24.0 * DEGREES_PER_HOUR - 0.001, 0);
+
+        assert_eq!((time.hours(), time.minutes()), (0, 0));
+    }
 }
@@ -0,0 +1,437 @@
+use crate::gcm;
+use crate::gcm::invalid_data;
+use std::io;
+use std::io::{BufReader, Read, Write};
+
+// Yaz0 is the run-length compression used by almost every archive on the disc.
+// The data is a 16 byte header followed by groups of up to eight chunks, each
+// group preceded by a byte whose bits (from the highest) say whether the chunk
+// is a single literal byte (1) or a back reference to earlier output (0).
+pub const MAGIC: &[u8; 4] = b"Yaz0";
+pub const HEADER_SIZE: usize = 0x10;
+
+const MAX_WINDOW: usize = 0x1000;
+const MIN_MATCH: usize = 3;
+// back references with a length of 0x12 or more use a third byte for it.
+const LONG_MATCH: usize = 0x12;
+const MAX_MATCH: usize = 0xFF + LONG_MATCH;
+
+const HASH_BITS: usize = 15;
+const NO_POSITION: usize = usize::MAX;
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.len() >= HEADER_SIZE && &data[0..4] == MAGIC
+}
+
+// decompressed_size returns the size of the data once decompressed, as
+// recorded in the header.
+pub fn decompressed_size(data: &[u8]) -> io::Result<usize> {
+    if !is_compressed(data) {
+        return Err(invalid_data("missing Yaz0 header"));
+    }
+
+    Ok(gcm::be_u32(data, 4)? as usize)
+}
+
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    decompress_from(data)
+}
+
+// decompress_from decompresses Yaz0 data as it is read, so a compressed file
+// never needs to be held in memory in full.
+pub fn decompress_from<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(reader);
+    let mut header = [0u8; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+
+    let size = decompressed_size(&header)?;
+    let mut out = Vec::with_capacity(size);
+
+    while out.len() < size {
+        let code = read_u8(&mut reader)?;
+
+        for bit in (0..8).rev() {
+            if out.len() >= size {
+                break;
+            }
+
+            if code & (1 << bit) != 0 {
+                out.push(read_u8(&mut reader)?);
+                continue;
+            }
+
+            let b1 = read_u8(&mut reader)? as usize;
+            let b2 = read_u8(&mut reader)? as usize;
+            let distance = (((b1 & 0xF) << 8) | b2) + 1;
+            let length = match b1 >> 4 {
+                0 => read_u8(&mut reader)? as usize + LONG_MATCH,
+                n => n + 2,
+            };
+
+            if distance > out.len() {
+                return Err(invalid_data("Yaz0 back reference is out of range"));
+            }
+
+            // the source and destination can overlap, so copy one byte at a time.
+            let start = out.len() - distance;
+            for i in 0..length.min(size - out.len()) {
+                out.push(out[start + i]);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+// Level trades compression speed for ratio. `window` is how far back to look
+// for matches (at most 0x1000 bytes) and `max_chain` is how many candidate
+// matches to compare at each position. `lazy` defers a match by a byte when
+// doing so finds a longer one, which is what Nintendo's own encoder does.
+#[derive(Debug, Clone, Copy)]
+pub struct Level {
+    pub window: usize,
+    pub max_chain: usize,
+    pub lazy: bool,
+}
+
+impl Level {
+    pub fn new(window: usize, max_chain: usize, lazy: bool) -> Self {
+        Self {
+            window: window.clamp(1, MAX_WINDOW),
+            max_chain: max_chain.max(1),
+            lazy,
+        }
+    }
+
+    pub fn fast() -> Self {
+        Self::new(MAX_WINDOW, 8, false)
+    }
+
+    pub fn best() -> Self {
+        Self::new(MAX_WINDOW, MAX_WINDOW, true)
+    }
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self::new(MAX_WINDOW, 64, true)
+    }
+}
+
+pub fn compress(data: &[u8], level: Level) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_SIZE + data.len() + data.len() / 8 + 1);
+    // writing to a Vec can't fail.
+    let _ = compress_to(data, level, &mut out);
+
+    out
+}
+
+pub fn compress_to<W: Write>(data: &[u8], level: Level, writer: &mut W) -> io::Result<()> {
+    let size = u32::try_from(data.len())
+        .map_err(|_| invalid_data("data is too large to be Yaz0 compressed"))?;
+
+    let mut header = [0u8; HEADER_SIZE];
+    header[0..4].copy_from_slice(MAGIC);
+    header[4..8].copy_from_slice(&size.to_be_bytes());
+    writer.write_all(&header)?;
+
+    let mut matcher = Matcher::new(data, level);
+    let mut group = Vec::with_capacity(1 + 8 * 3);
+    let mut chunks = 0;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        if chunks == 0 {
+            group.clear();
+            group.push(0);
+        }
+
+        let (mut length, distance) = matcher.find(pos);
+        matcher.insert(pos);
+
+        // emit a literal instead if the next position starts a longer match.
+        if level.lazy
+            && (MIN_MATCH..MAX_MATCH).contains(&length)
+            && matcher.find(pos + 1).0 > length + 1
+        {
+            length = 0;
+        }
+
+        if length >= MIN_MATCH {
+            let distance = distance - 1;
+            if length >= LONG_MATCH {
+                group.push((distance >> 8) as u8);
+                group.push(distance as u8);
+                group.push((length - LONG_MATCH) as u8);
+            } else {
+                group.push((((length - 2) << 4) | (distance >> 8)) as u8);
+                group.push(distance as u8);
+            }
+
+            matcher.insert_range(pos + 1, pos + length);
+            pos += length;
+        } else {
+            group[0] |= 0x80 >> chunks;
+            group.push(data[pos]);
+            pos += 1;
+        }
+
+        chunks += 1;
+        if chunks == 8 || pos >= data.len() {
+            writer.write_all(&group)?;
+            chunks = 0;
+        }
+    }
+
+    Ok(())
+}
+
+// Matcher finds back references using hash chains of every 3 byte sequence
+// within the search window.
+struct Matcher<'a> {
+    data: &'a [u8],
+    level: Level,
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(data: &'a [u8], level: Level) -> Self {
+        Self {
+            data,
+            level,
+            head: vec![NO_POSITION; 1 << HASH_BITS],
+            prev: vec![NO_POSITION; level.window],
+        }
+    }
+
+    fn hash(&self, pos: usize) -> usize {
+        let bytes = &self.data[pos..pos + MIN_MATCH];
+        let h = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+
+        (h.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if pos + MIN_MATCH > self.data.len() {
+            return;
+        }
+
+        let h = self.hash(pos);
+        self.prev[pos % self.level.window] = self.head[h];
+        self.head[h] = pos;
+    }
+
+    fn insert_range(&mut self, from: usize, to: usize) {
+        for pos in from..to {
+            self.insert(pos);
+        }
+    }
+
+    // find returns the length and distance of the longest match for the data
+    // at pos, or a length of 0 if there isn't one.
+    fn find(&self, pos: usize) -> (usize, usize) {
+        if pos + MIN_MATCH > self.data.len() {
+            return (0, 0);
+        }
+
+        let max = MAX_MATCH.min(self.data.len() - pos);
+        let mut best = (0, 0);
+        let mut candidate = self.head[self.hash(pos)];
+        let mut chain = 0;
+
+        while candidate != NO_POSITION
+            && candidate < pos
+            && pos - candidate <= self.level.window
+            && chain < self.level.max_chain
+        {
+            let length = self.data[candidate..]
+                .iter()
+                .zip(&self.data[pos..pos + max])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            if length > best.0 {
+                best = (length, pos - candidate);
+                if length == max {
+                    break;
+                }
+            }
+
+            let next = self.prev[candidate % self.level.window];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain += 1;
+        }
+
+        best
+    }
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+
+    Ok(buf[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels() -> [Level; 4] {
+        [
+            Level::fast(),
+            Level::default(),
+            Level::best(),
+            Level::new(16, 1, true),
+        ]
+    }
+
+    // sample mixes random literals with copies of earlier data at a range of
+    // distances and lengths, including copies that overlap their source.
+    fn sample(size: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2654435761).wrapping_add(1);
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let r = next();
+            if data.len() < MIN_MATCH || r % 3 == 0 {
+                data.push(next() as u8);
+                continue;
+            }
+
+            let distance = 1 + next() as usize % data.len().min(MAX_WINDOW + 0x10);
+            let length = match r % 4 {
+                0 => MIN_MATCH + next() as usize % (LONG_MATCH - MIN_MATCH),
+                1 => LONG_MATCH + next() as usize % (MAX_MATCH + 0x10 - LONG_MATCH),
+                _ => 1 + next() as usize % 8,
+            };
+
+            let start = data.len() - distance;
+            for i in 0..length.min(size - data.len()) {
+                data.push(data[start + i]);
+            }
+        }
+
+        data
+    }
+
+    fn assert_round_trip(data: &[u8]) {
+        for level in levels() {
+            let compressed = compress(data, level);
+
+            assert!(is_compressed(&compressed));
+            assert_eq!(decompressed_size(&compressed).unwrap(), data.len());
+            assert_eq!(decompress(&compressed).unwrap(), data, "{:?}", level);
+        }
+    }
+
+    #[test]
+    fn round_trip_sizes() {
+        let sizes = [
+            0, 1, 2, 3, 4, 7, 8, 9, 16, 17, 18, 19, 0x111, 0x112, 0x113, 0xFFF, 0x1000, 0x1001,
+            0x1234, 0xFFFF, 0x10000, 0x10001, 69_999,
+        ];
+
+        for (seed, size) in sizes.into_iter().enumerate() {
+            assert_round_trip(&sample(size, seed as u32));
+        }
+    }
+
+    #[test]
+    fn round_trip_short_matches() {
+        for length in MIN_MATCH..LONG_MATCH {
+            let mut data = b"0123456789abcdefghijklmnopqrstuvwxyz".to_vec();
+            data.extend_from_within(4..4 + length);
+            data.extend_from_slice(b"!");
+            data.extend_from_within(0..length);
+
+            assert_round_trip(&data);
+        }
+    }
+
+    #[test]
+    fn round_trip_long_matches() {
+        let block: Vec<u8> = (0..MAX_MATCH + 0x20)
+            .map(|i| (i * 7 + i / 13) as u8)
+            .collect();
+
+        for length in [LONG_MATCH, LONG_MATCH + 1, 0x80, MAX_MATCH, MAX_MATCH + 1] {
+            let mut data = block.clone();
+            data.push(0xAA);
+            data.extend_from_slice(&block[..length]);
+
+            assert_round_trip(&data);
+        }
+    }
+
+    #[test]
+    fn round_trip_overlapping() {
+        assert_round_trip(&[0u8; 0x1000]);
+        assert_round_trip(&b"ab".repeat(0x400));
+        assert_round_trip(&b"abc".repeat(0x155));
+        assert_round_trip(&[b"xyz".as_slice(), &[7u8; MAX_MATCH * 3], b"xyz"].concat());
+    }
+
+    #[test]
+    fn compresses_repeated_data() {
+        for level in levels() {
+            assert!(compress(&[0u8; 0x1000], level).len() < 0x100);
+        }
+    }
+
+    #[test]
+    fn decompress_overlapping_reference() {
+        // a literal followed by a short and a long back reference, both one
+        // byte behind the output.
+        let data = [
+            b'Y',
+            b'a',
+            b'z',
+            b'0',
+            0,
+            0,
+            0,
+            0x18,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0, //
+            0b1000_0000,
+            b'a',
+            0x30,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+        ];
+
+        assert_eq!(decompressed_size(&data).unwrap(), 0x18);
+        assert_eq!(decompress(&data).unwrap(), vec![b'a'; 0x18]);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(decompressed_size(b"Yaz1\0\0\0\x01").is_err());
+        // a back reference before the start of the output.
+        let data = [
+            b'Y', b'a', b'z', b'0', 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x20, 0x00,
+        ];
+        assert!(decompress(&data).is_err());
+        // truncated data.
+        assert!(decompress(&compress(b"hello hello hello", Level::default())[..18]).is_err());
+    }
+}